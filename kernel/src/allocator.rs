@@ -3,14 +3,13 @@ static ALLOCATOR: DummyAllocator = DummyAllocator;
 
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::ptr::null_mut;
-use core::fmt::Write;
+use log::trace;
 
-use crate::serial;
 pub struct DummyAllocator;
 
 pub static mut HEAP_START: usize = 0x0;
 pub static mut OFFSET: usize = 0x0;
-pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
+pub const HEAP_SIZE: usize = 32 * 1024 * 1024; // 32 MiB, enough for a 1080p back buffer
 
 unsafe impl GlobalAlloc for DummyAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
@@ -36,7 +35,7 @@ unsafe impl GlobalAlloc for DummyAllocator {
     }
 
     unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
-        writeln!(serial(), "dealloc was called at {_ptr:?}").unwrap();
+        trace!("dealloc was called at {_ptr:?}");
         // Note: Bump allocator doesn't actually free memory
     }
 }
@@ -46,4 +45,9 @@ pub fn init_heap(offset: usize) {
         HEAP_START = offset;
         OFFSET = 0;
     }
-}
\ No newline at end of file
+}
+
+/// Bytes handed out so far, including alignment padding.
+pub fn used() -> usize {
+    unsafe { OFFSET }
+}
@@ -1,18 +1,22 @@
 use bootloader_api::info::MemoryRegionKind::Usable;
 use bootloader_api::info::MemoryRegions;
+use core::ops::Range;
 use x86_64::registers::control::Cr3;
 use x86_64::structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB};
 use x86_64::{PhysAddr, VirtAddr};
 
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryRegions,
+    /// Physical memory already in use, i.e. the heap, which must not be handed out as frames.
+    reserved: Range<u64>,
     next: usize,
 }
 
 impl BootInfoFrameAllocator {
-    pub fn new(memory_map: &'static MemoryRegions) -> Self {
+    pub fn new(memory_map: &'static MemoryRegions, reserved: Range<u64>) -> Self {
         BootInfoFrameAllocator {
             memory_map,
+            reserved,
             next: 0,
         }
     }
@@ -21,10 +25,17 @@ impl BootInfoFrameAllocator {
 
         let usable_regions = regions.filter(|region| region.kind == Usable);
         let address_ranges = usable_regions.map(|region| region.start..region.end);
-        let frame_addresses = address_ranges.flat_map(|region| region.step_by(4096));
+        let frame_addresses = address_ranges
+            .flat_map(|region| region.step_by(4096))
+            .filter(|address| !self.reserved.contains(address));
 
         frame_addresses.map(|address| PhysFrame::containing_address(PhysAddr::new(address)))
     }
+
+    /// Number of frames handed out so far.
+    pub fn allocated(&self) -> usize {
+        self.next
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
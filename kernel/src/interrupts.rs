@@ -1,10 +1,10 @@
-use core::fmt::Write;
 use core::ptr::NonNull;
-use crate::serial;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
+use log::{debug, info, warn};
 use spin::Mutex;
 use x86_64::{PhysAddr, VirtAddr};
-use crate::HandlerTable;
+use crate::{HandlerTable, serial};
 use acpi::{AcpiHandler, AcpiTables, PhysicalMapping};
 use pc_keyboard::{layouts, HandleControl, Keyboard, ScancodeSet1};
 use x86_64::registers::control::Cr2;
@@ -111,6 +111,32 @@ pub enum APICOffset {
     R0x3F0 = 0x3F0,   // RESERVED = 0x3F0
 }
 
+impl APICOffset {
+    /// Registers that can be read, in address order. The rest are reserved or write-only.
+    pub const READABLE: [APICOffset; 42] = [
+        APICOffset::Ir, APICOffset::Vr, APICOffset::Tpr, APICOffset::Apr, APICOffset::Ppr,
+        APICOffset::Ldr, APICOffset::Dfr, APICOffset::Svr,
+        APICOffset::Isr1, APICOffset::Isr2, APICOffset::Isr3, APICOffset::Isr4,
+        APICOffset::Isr5, APICOffset::Isr6, APICOffset::Isr7, APICOffset::Isr8,
+        APICOffset::Tmr1, APICOffset::Tmr2, APICOffset::Tmr3, APICOffset::Tmr4,
+        APICOffset::Tmr5, APICOffset::Tmr6, APICOffset::Tmr7, APICOffset::Tmr8,
+        APICOffset::Irr1, APICOffset::Irr2, APICOffset::Irr3, APICOffset::Irr4,
+        APICOffset::Irr5, APICOffset::Irr6, APICOffset::Irr7, APICOffset::Irr8,
+        APICOffset::Esr, APICOffset::Icr1, APICOffset::Icr2, APICOffset::LvtT,
+        APICOffset::LvtLint0, APICOffset::LvtLint1, APICOffset::LvtE,
+        APICOffset::Ticr, APICOffset::Tccr, APICOffset::Tdcr,
+    ];
+}
+
+/// Reads a local APIC register. Returns None before the APIC has been mapped.
+pub fn read_lapic(offset: APICOffset) -> Option<u32> {
+    let lapic = LAPIC_ADDR.lock();
+    if lapic.address.is_null() {
+        return None;
+    }
+    Some(unsafe { lapic.address.offset(offset as isize / 4).read_volatile() })
+}
+
 pub struct AcpiHandlerImpl {
     physical_memory_offset: VirtAddr,
 }
@@ -169,6 +195,8 @@ lazy_static! {
 
         idt[InterruptIndex::Timer as u8].set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard as u8].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Com2 as u8].set_handler_fn(com2_interrupt_handler);
+        idt[InterruptIndex::Com1 as u8].set_handler_fn(com1_interrupt_handler);
 
         idt
     };
@@ -185,10 +213,21 @@ unsafe fn init_io_apic(
     let ioapic_pointer = virt_addr.as_mut_ptr::<u32>();
 
     unsafe {
-        ioapic_pointer.offset(0).write_volatile(0x12);
-        ioapic_pointer
-            .offset(4)
-            .write_volatile(InterruptIndex::Keyboard as u8 as u32);
+        route_irq(ioapic_pointer, 1, InterruptIndex::Keyboard);
+        route_irq(ioapic_pointer, 3, InterruptIndex::Com2);
+        route_irq(ioapic_pointer, 4, InterruptIndex::Com1);
+    }
+}
+
+/// Points the I/O APIC redirection entry for an ISA IRQ at `vector` on the boot processor.
+unsafe fn route_irq(ioapic_pointer: *mut u32, irq: u8, vector: InterruptIndex) {
+    let entry = 0x10 + 2 * irq as u32;
+    unsafe {
+        // IOREGSEL at offset 0x00 selects the register, IOWIN at offset 0x10 accesses it
+        ioapic_pointer.offset(0).write_volatile(entry);
+        ioapic_pointer.offset(4).write_volatile(vector as u8 as u32);
+        ioapic_pointer.offset(0).write_volatile(entry + 1);
+        ioapic_pointer.offset(4).write_volatile(0);
     }
 }
 
@@ -205,22 +244,65 @@ unsafe fn init_local_apic(
         init_timer(lapic_pointer);
         init_keyboard(lapic_pointer);
     }
-    writeln!(serial(), "init LAPIC_ADDR {:?}", LAPIC_ADDR.lock()).unwrap();
+    debug!("init LAPIC_ADDR {:?}", LAPIC_ADDR.lock());
 }
 
+/// Rate of the LAPIC timer interrupt, and so of game frames.
+pub const TIMER_HZ: u32 = 60;
+const PIT_HZ: u32 = 1_193_182;
+/// Length of the PIT one-shot the LAPIC timer is calibrated against.
+const CALIBRATION_MS: u32 = 10;
+
 unsafe fn init_timer(lapic_pointer: *mut u32) {
     unsafe {
         let svr = lapic_pointer.offset(APICOffset::Svr as isize / 4);
         svr.write_volatile(svr.read_volatile() | 0x100); // Set bit 8
 
-        let lvt_lint1 = lapic_pointer.offset(APICOffset::LvtT as isize / 4);
-        lvt_lint1.write_volatile(0x20 | (1 << 17)); // Vector 0x20, periodic mode
-
         let tdcr = lapic_pointer.offset(APICOffset::Tdcr as isize / 4);
         tdcr.write_volatile(0x3); // Divide by 16 mode
 
+        let ticks_per_calibration = calibrate_timer(lapic_pointer);
+        let initial_count = ticks_per_calibration * (1000 / CALIBRATION_MS) / TIMER_HZ;
+        debug!("LAPIC timer: {ticks_per_calibration} ticks per {CALIBRATION_MS} ms, initial count {initial_count}");
+
+        let lvt_timer = lapic_pointer.offset(APICOffset::LvtT as isize / 4);
+        lvt_timer.write_volatile(0x20 | (1 << 17)); // Vector 0x20, periodic mode
+
         let ticr = lapic_pointer.offset(APICOffset::Ticr as isize / 4);
-        ticr.write_volatile(0x0400_0000); // An arbitrary value for the initial value of the timer
+        ticr.write_volatile(initial_count.max(1));
+    }
+}
+
+/// Counts LAPIC timer ticks during a `CALIBRATION_MS` one-shot of PIT channel 2, whose
+/// frequency is fixed.
+unsafe fn calibrate_timer(lapic_pointer: *mut u32) -> u32 {
+    let mut speaker = Port::<u8>::new(0x61);
+    let mut pit_command = Port::<u8>::new(0x43);
+    let mut pit_channel2 = Port::<u8>::new(0x42);
+    let count = PIT_HZ * CALIBRATION_MS / 1000;
+
+    unsafe {
+        // Masked one-shot so the LAPIC just counts down
+        let lvt_timer = lapic_pointer.offset(APICOffset::LvtT as isize / 4);
+        lvt_timer.write_volatile(1 << 16);
+
+        // Channel 2 gate on, speaker off; channel 2, low then high byte, mode 0
+        let control = speaker.read();
+        speaker.write((control & 0xFC) | 0x01);
+        pit_command.write(0xB0);
+        pit_channel2.write(count as u8);
+        pit_channel2.write((count >> 8) as u8);
+
+        let ticr = lapic_pointer.offset(APICOffset::Ticr as isize / 4);
+        let tccr = lapic_pointer.offset(APICOffset::Tccr as isize / 4);
+        ticr.write_volatile(u32::MAX);
+        // Bit 5 goes high when the channel 2 count reaches zero
+        while speaker.read() & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+        let remaining = tccr.read_volatile();
+        ticr.write_volatile(0);
+        u32::MAX - remaining
     }
 }
 
@@ -275,8 +357,8 @@ pub fn init_apic(rsdp: usize, offset: u64, mapper: &mut impl Mapper<Size4KiB>, f
 
     disable_pic();
 
-    writeln!(serial(), "APIC setup completed, pending interrupt and setup IDT.").unwrap();
-    writeln!(serial(), "LAPIC address: {:?}", LAPIC_ADDR.lock()).unwrap();
+    info!("APIC setup completed, pending interrupt and setup IDT.");
+    debug!("LAPIC address: {:?}", LAPIC_ADDR.lock());
     LAPIC_ADDR.lock().address
 }
 
@@ -297,17 +379,18 @@ fn end_interrupt() {
 /// Initializes the interrupt table with the given interrupt handlers.
 pub fn init_idt(handlers: HandlerTable, lapic_pointer: *mut u32) {
     LAPIC_ADDR.lock().address = lapic_pointer;
-    writeln!(serial(), "initialize IDT with LAPIC_ADDR {:?}", LAPIC_ADDR.lock()).unwrap();
+    debug!("initialize IDT with LAPIC_ADDR {:?}", LAPIC_ADDR.lock());
     *(HANDLERS.lock()) = Some(handlers);
 
     IDT.load();
     x86_64::instructions::interrupts::enable();
+    serial::enable_interrupts();
 }
 
 extern "x86-interrupt" fn breakpoint_handler(
     stack_frame: InterruptStackFrame)
 {
-    writeln!(serial(), "EXCEPTION: BREAKPOINT\n{:#?}", stack_frame).unwrap();
+    warn!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
@@ -320,16 +403,37 @@ extern "x86-interrupt" fn double_fault_handler(
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
+/// Names of the hardware interrupts counted by `irq_counts`, in `InterruptIndex` order.
+pub const IRQ_NAMES: [&str; 4] = ["timer", "keyboard", "com2", "com1"];
+static IRQ_COUNTS: [AtomicU64; IRQ_NAMES.len()] = [const { AtomicU64::new(0) }; IRQ_NAMES.len()];
+
+/// Number of interrupts received from each source in `IRQ_NAMES`.
+pub fn irq_counts() -> [u64; IRQ_NAMES.len()] {
+    core::array::from_fn(|i| IRQ_COUNTS[i].load(Ordering::Relaxed))
+}
+
+fn count_irq(index: InterruptIndex) {
+    let slot = match index {
+        InterruptIndex::Timer => 0,
+        InterruptIndex::Keyboard => 1,
+        InterruptIndex::Com2 => 2,
+        InterruptIndex::Com1 => 3,
+    };
+    IRQ_COUNTS[slot].fetch_add(1, Ordering::Relaxed);
+}
+
 const PIC_1_OFFSET: u8 = 0x20;
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    Com2 = PIC_1_OFFSET + 3,
+    Com1 = PIC_1_OFFSET + 4,
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    
+    count_irq(InterruptIndex::Timer);
     let h = &*HANDLERS.lock();
     if let Some(handler) = h {
         handler.handle_timer();
@@ -339,6 +443,7 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    count_irq(InterruptIndex::Keyboard);
 
     lazy_static! {
         static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
@@ -362,4 +467,16 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
 
     end_interrupt();
 
-}
\ No newline at end of file
+}
+
+extern "x86-interrupt" fn com1_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    count_irq(InterruptIndex::Com1);
+    serial::handle_interrupt(4);
+    end_interrupt();
+}
+
+extern "x86-interrupt" fn com2_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    count_irq(InterruptIndex::Com2);
+    serial::handle_interrupt(3);
+    end_interrupt();
+}
@@ -5,17 +5,27 @@
 use core::cell::UnsafeCell;
 use core::panic::PanicInfo;
 use core::fmt::Write;
-use uart_16550::SerialPort;
+use core::sync::atomic::{AtomicU64, Ordering};
 use pc_keyboard::DecodedKey;
 
 mod interrupts;
+pub mod logger;
+pub mod serial;
 
 extern crate alloc;
 
-pub fn serial() -> SerialPort {
-    let mut port = unsafe { SerialPort::new(0x3F8) };
-    port.init();
-    port
+/// Writer for COM1, the port QEMU connects to `-serial stdio`.
+pub fn serial() -> serial::Serial {
+    serial::Serial(serial::ComPort::Com1)
+}
+
+pub use interrupts::{irq_counts, IRQ_NAMES, TIMER_HZ};
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of timer interrupts since the handlers were started.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
 }
 
 /// Table of interrupt handlers. This struct uses the
@@ -57,6 +67,7 @@ impl HandlerTable {
 
     /// Called by the low-level interrupt routines to handle a timer event.
     pub fn handle_timer(&self) {
+        TICKS.fetch_add(1, Ordering::Relaxed);
         if let Some(timer) = self.timer {
             (timer)()
         }
@@ -102,7 +113,10 @@ pub fn hlt_loop() -> ! {
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    let _ = writeln!(serial(), "PANIC: {info}");
+    let mut out = serial::EmergencySerial;
+    let _ = writeln!(out, "PANIC: {info}");
+    let _ = writeln!(out, "--- recent log ---");
+    let _ = logger::dump(&mut out);
     hlt_loop();
 }
 
@@ -0,0 +1,163 @@
+//! Kernel implementation of the [log](https://docs.rs/log) facade.
+//!
+//! Every record is timestamped with the timer tick count and written to the serial port, to an
+//! in-memory ring buffer that the panic handler dumps after a crash, and optionally to a console
+//! set with `set_console` (the kernel binary points it at the screen while it boots).
+//!
+//! The level can be set globally with `set_level` and per module with `set_module_level`;
+//! the longest matching target prefix wins.
+
+use core::fmt::{self, Write};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+use crate::serial;
+
+const RING_SIZE: usize = 16 * 1024;
+const MAX_MODULE_FILTERS: usize = 8;
+
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+static LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+static MODULE_LEVELS: Mutex<[Option<(&'static str, LevelFilter)>; MAX_MODULE_FILTERS]> =
+    Mutex::new([None; MAX_MODULE_FILTERS]);
+static CONSOLE: Mutex<Option<fn(fmt::Arguments)>> = Mutex::new(None);
+static RING: Mutex<RingBuffer> = Mutex::new(RingBuffer::new());
+
+/// Installs the kernel logger. Records more verbose than `level` are dropped unless a module
+/// filter lets them through.
+pub fn init(level: LevelFilter) {
+    set_level(level);
+    // Filtering happens in `enabled`, so let every record reach the logger
+    log::set_max_level(LevelFilter::Trace);
+    log::set_logger(&LOGGER).expect("logger already initialized");
+}
+
+pub fn set_level(level: LevelFilter) {
+    *LEVEL.lock() = level;
+}
+
+/// Overrides the level for every target starting with `prefix`, e.g. `"kernel::allocator"`.
+/// Returns false if all filter slots are taken.
+pub fn set_module_level(prefix: &'static str, level: LevelFilter) -> bool {
+    let mut filters = MODULE_LEVELS.lock();
+    let slot = filters.iter().position(|f| matches!(f, Some((p, _)) if *p == prefix))
+        .or_else(|| filters.iter().position(Option::is_none));
+    match slot {
+        Some(index) => {
+            filters[index] = Some((prefix, level));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Sets (or removes) an extra output for log records besides serial and the ring buffer.
+pub fn set_console(console: Option<fn(fmt::Arguments)>) {
+    *CONSOLE.lock() = console;
+}
+
+/// Writes the buffered log, oldest record first. Gives up silently if the buffer is locked,
+/// which can happen when called from a panic raised while logging.
+pub fn dump(out: &mut impl Write) -> fmt::Result {
+    match RING.try_lock() {
+        Some(ring) => ring.dump(out),
+        None => Ok(()),
+    }
+}
+
+fn level_for(target: &str) -> LevelFilter {
+    let filters = MODULE_LEVELS.lock();
+    filters.iter()
+        .flatten()
+        .filter(|(prefix, _)| target.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(*LEVEL.lock(), |&(_, level)| level)
+}
+
+/// ANSI colour sequence used for the level tag on serial and the screen console.
+fn level_color(level: Level) -> &'static str {
+    match level {
+        Level::Error => "\x1b[31m",
+        Level::Warn => "\x1b[33m",
+        Level::Info => "\x1b[32m",
+        Level::Debug => "\x1b[36m",
+        Level::Trace => "\x1b[90m",
+    }
+}
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let ticks = crate::ticks();
+        let level = record.level();
+        let color = level_color(level);
+        let target = record.target();
+        let args = record.args();
+
+        let _ = writeln!(serial(), "[{ticks:>8}] {color}{level:<5}\x1b[0m {target}: {args}");
+        if let Some(mut ring) = RING.try_lock() {
+            let _ = writeln!(ring, "[{ticks:>8}] {level:<5} {target}: {args}");
+        }
+        if let Some(console) = *CONSOLE.lock() {
+            console(format_args!("[{ticks:>8}] {color}{level:<5}\x1b[0m {target}: {args}\n"));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Fixed-size byte ring that keeps the most recent `RING_SIZE` bytes of log output.
+struct RingBuffer {
+    data: [u8; RING_SIZE],
+    /// Index the next byte is written to.
+    head: usize,
+    wrapped: bool,
+}
+
+impl RingBuffer {
+    const fn new() -> Self {
+        RingBuffer { data: [0; RING_SIZE], head: 0, wrapped: false }
+    }
+
+    fn dump(&self, out: &mut impl Write) -> fmt::Result {
+        let (older, newer) = if self.wrapped {
+            (&self.data[self.head..], &self.data[..self.head])
+        } else {
+            (&[][..], &self.data[..self.head])
+        };
+        // After wrapping, the oldest line has lost its start, so skip to the next full line
+        let older = match older.iter().position(|&b| b == b'\n') {
+            Some(newline) => &older[newline + 1..],
+            None => &[][..],
+        };
+        for chunk in older.utf8_chunks().chain(newer.utf8_chunks()) {
+            out.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                out.write_char(char::REPLACEMENT_CHARACTER)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.data[self.head] = byte;
+            self.head += 1;
+            if self.head == RING_SIZE {
+                self.head = 0;
+                self.wrapped = true;
+            }
+        }
+        Ok(())
+    }
+}
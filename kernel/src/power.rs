@@ -0,0 +1,187 @@
+//! Power-off and reset.
+//!
+//! Shutdown enters ACPI sleep state S5 by writing `SLP_TYPx | SLP_EN` to the PM1 control
+//! registers from the FADT. The `SLP_TYPx` values come from the `\_S5_` package in the DSDT,
+//! which we find by scanning the AML bytes rather than running an interpreter.
+//!
+//! Reset tries the FADT reset register, then the 8042 keyboard controller's reset line, then
+//! a triple fault.
+
+use acpi::address::{AddressSpace, GenericAddress};
+use acpi::fadt::Fadt;
+use acpi::AcpiTables;
+use log::{error, info, warn};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use x86_64::VirtAddr;
+use crate::interrupts::AcpiHandlerImpl;
+
+const SLP_EN: u16 = 1 << 13;
+const SCI_EN: u16 = 1;
+
+#[derive(Clone, Copy)]
+struct AcpiPower {
+    pm1a_control: u16,
+    pm1b_control: Option<u16>,
+    /// `SLP_TYPa` and `SLP_TYPb` for S5.
+    s5: Option<(u8, u8)>,
+    smi_command: u16,
+    acpi_enable: u8,
+    reset: Option<(GenericAddress, u8)>,
+    physical_offset: u64,
+}
+
+static POWER: Mutex<Option<AcpiPower>> = Mutex::new(None);
+
+/// Reads what shutdown and reset need from the ACPI tables.
+pub fn init(rsdp: usize, physical_offset: u64) {
+    let handler = AcpiHandlerImpl::new(VirtAddr::new(physical_offset));
+    let tables = match unsafe { AcpiTables::from_rsdp(handler, rsdp) } {
+        Ok(tables) => tables,
+        Err(e) => {
+            warn!("No ACPI power management: {e:?}");
+            return;
+        }
+    };
+    let Ok(fadt) = tables.find_table::<Fadt>() else {
+        warn!("No FADT, ACPI shutdown and reset unavailable");
+        return;
+    };
+
+    let io_port = |address: GenericAddress| match address.address_space {
+        AddressSpace::SystemIo => Some(address.address as u16),
+        _ => None,
+    };
+    let Some(pm1a_control) = fadt.pm1a_control_block().ok().and_then(io_port) else {
+        warn!("PM1a control block is not in I/O space, ACPI shutdown unavailable");
+        return;
+    };
+    let pm1b_control = fadt.pm1b_control_block().ok().flatten().and_then(io_port);
+    // Copy out of the packed table before calling methods on it
+    let flags = fadt.flags;
+    let reset = match fadt.reset_register() {
+        Ok(register) if flags.supports_system_reset_via_fadt() => Some((register, fadt.reset_value)),
+        _ => None,
+    };
+    let s5 = tables.dsdt().ok().and_then(|dsdt| {
+        let aml = unsafe {
+            core::slice::from_raw_parts((physical_offset + dsdt.address as u64) as *const u8, dsdt.length as usize)
+        };
+        find_s5(aml)
+    });
+    if s5.is_none() {
+        warn!("No \\_S5_ package in the DSDT, ACPI shutdown unavailable");
+    }
+
+    *POWER.lock() = Some(AcpiPower {
+        pm1a_control,
+        pm1b_control,
+        s5,
+        smi_command: fadt.smi_cmd_port as u16,
+        acpi_enable: fadt.acpi_enable,
+        reset,
+        physical_offset,
+    });
+    info!("ACPI power: PM1a {pm1a_control:#x}, S5 {s5:?}, reset register {}", reset.is_some());
+}
+
+/// Finds `Name(\_S5_, Package() { SLP_TYPa, SLP_TYPb, ... })` in AML and returns the two
+/// sleep type values. `_S5_` can also appear elsewhere, e.g. referenced inside a method, so
+/// every occurrence is tried.
+fn find_s5(aml: &[u8]) -> Option<(u8, u8)> {
+    aml.windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"_S5_")
+        .find_map(|(start, _)| parse_s5(aml, start))
+}
+
+/// Reads the sleep type values of the `_S5_` name at `start`, if it's the `Name` definition.
+fn parse_s5(aml: &[u8], start: usize) -> Option<(u8, u8)> {
+    // Must be the operand of a NameOp (0x08), possibly with a root prefix '\'
+    let name_op = match start {
+        1.. if aml[start - 1] == 0x08 => true,
+        2.. if aml[start - 1] == b'\\' && aml[start - 2] == 0x08 => true,
+        _ => false,
+    };
+    let mut bytes = aml.get(start + 4..)?.iter().copied();
+    if !name_op || bytes.next()? != 0x12 {
+        return None; // Not followed by a PackageOp
+    }
+    // PkgLength: bits 6-7 of the lead byte count the bytes that follow it
+    let lead = bytes.next()?;
+    for _ in 0..(lead >> 6) {
+        bytes.next()?;
+    }
+    let _element_count = bytes.next()?;
+    let mut integer = || match bytes.next()? {
+        0x0A => bytes.next(), // BytePrefix
+        0x00 => Some(0),      // ZeroOp
+        0x01 => Some(1),      // OneOp
+        _ => None,
+    };
+    Some((integer()?, integer()?))
+}
+
+/// Powers the machine off. Falls back to halting if ACPI shutdown is unavailable or fails.
+pub fn shutdown() -> ! {
+    info!("Powering off");
+    x86_64::instructions::interrupts::disable();
+    let power = *POWER.lock();
+    if let Some(AcpiPower { pm1a_control, pm1b_control, s5: Some((typ_a, typ_b)), smi_command, acpi_enable, .. }) = power {
+        unsafe {
+            let mut control = Port::<u16>::new(pm1a_control);
+            // Hand the ACPI registers over from SMM first if the firmware hasn't
+            if control.read() & SCI_EN == 0 && smi_command != 0 && acpi_enable != 0 {
+                Port::<u8>::new(smi_command).write(acpi_enable);
+                for _ in 0..1_000_000 {
+                    if control.read() & SCI_EN != 0 {
+                        break;
+                    }
+                    core::hint::spin_loop();
+                }
+            }
+            control.write(((typ_a as u16) << 10) | SLP_EN);
+            if let Some(pm1b_control) = pm1b_control {
+                Port::<u16>::new(pm1b_control).write(((typ_b as u16) << 10) | SLP_EN);
+            }
+        }
+    }
+    error!("ACPI shutdown failed, halting");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Resets the machine.
+pub fn reboot() -> ! {
+    info!("Rebooting");
+    x86_64::instructions::interrupts::disable();
+    let power = *POWER.lock();
+    if let Some(AcpiPower { reset: Some((register, value)), physical_offset, .. }) = power {
+        match register.address_space {
+            AddressSpace::SystemIo => unsafe { Port::<u8>::new(register.address as u16).write(value) },
+            AddressSpace::SystemMemory => unsafe {
+                ((physical_offset + register.address) as *mut u8).write_volatile(value)
+            },
+            space => warn!("Reset register in unsupported address space {space:?}"),
+        }
+    }
+
+    unsafe {
+        // Pulse the CPU reset line through the 8042 keyboard controller
+        let mut status = Port::<u8>::new(0x64);
+        for _ in 0..1_000_000 {
+            if status.read() & 0x02 == 0 {
+                break;
+            }
+        }
+        status.write(0xFE);
+
+        // Last resort, a triple fault: with an empty IDT any exception is fatal
+        x86_64::instructions::tables::lidt(&x86_64::structures::DescriptorTablePointer {
+            limit: 0,
+            base: VirtAddr::new(0),
+        });
+        core::arch::asm!("int3", options(noreturn));
+    }
+}
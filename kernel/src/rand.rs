@@ -0,0 +1,77 @@
+//! Seeds for the game's random number generator. The CPU's hardware generator is used when
+//! CPUID reports one (RDSEED, then RDRAND), otherwise the time stamp counter mixed with the
+//! CMOS real-time clock, so every boot serves differently. The shell's `seed` command and the
+//! remote protocol can replace the seed to replay a match.
+
+use core::arch::x86_64::{__cpuid, __cpuid_count, _rdrand64_step, _rdseed64_step};
+use x86_64::instructions::port::Port;
+
+/// The hardware generators can run dry briefly; Intel suggests retrying RDRAND 10 times.
+const RETRIES: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Source {
+    Rdseed,
+    Rdrand,
+    Clock,
+}
+
+/// Returns a fresh seed and where it came from.
+pub fn seed() -> (u64, Source) {
+    // SAFETY: each instruction is only used when CPUID reports it
+    if has_rdseed() && let Some(seed) = unsafe { rdseed() } {
+        return (seed, Source::Rdseed);
+    }
+    if has_rdrand() && let Some(seed) = unsafe { rdrand() } {
+        return (seed, Source::Rdrand);
+    }
+    let tsc = unsafe { core::arch::x86_64::_rdtsc() };
+    (mix(tsc) ^ rtc(), Source::Clock)
+}
+
+fn has_rdrand() -> bool {
+    __cpuid(1).ecx & (1 << 30) != 0
+}
+
+fn has_rdseed() -> bool {
+    __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 18) != 0
+}
+
+#[target_feature(enable = "rdseed")]
+fn rdseed() -> Option<u64> {
+    let mut value = 0;
+    (0..RETRIES).find_map(|_| (_rdseed64_step(&mut value) == 1).then_some(value))
+}
+
+#[target_feature(enable = "rdrand")]
+fn rdrand() -> Option<u64> {
+    let mut value = 0;
+    (0..RETRIES).find_map(|_| (_rdrand64_step(&mut value) == 1).then_some(value))
+}
+
+/// Date and time from the CMOS clock, packed one field per byte. Only used as entropy, so the
+/// BCD or binary encoding doesn't matter.
+fn rtc() -> u64 {
+    let mut index = Port::<u8>::new(0x70);
+    let mut data = Port::<u8>::new(0x71);
+    let mut read = |register: u8| unsafe {
+        index.write(register);
+        data.read()
+    };
+    // Wait out an update in progress so the fields belong to the same second
+    for _ in 0..10_000 {
+        if read(0x0A) & 0x80 == 0 {
+            break;
+        }
+    }
+    // Seconds, minutes, hours, day, month, year
+    [0x00, 0x02, 0x04, 0x07, 0x08, 0x09].iter().fold(0, |time, &register| (time << 8) | read(register) as u64)
+}
+
+/// SplitMix64 finalizer, spreading the few changing low bits of the TSC over the whole word.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
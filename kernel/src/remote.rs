@@ -0,0 +1,150 @@
+//! Line-based control protocol for scripted players, entered with the shell's `remote`
+//! command. Nothing is echoed; every request line gets exactly one JSON line back, either
+//! `{"ok":true,...}` or `{"ok":false,"error":"..."}`. Log records share the port, so clients
+//! should skip lines that don't start with `{`.
+//!
+//! - `state`: ball, paddles, scores, mode and players
+//! - `move <left|right> <up|down> [steps]`: same as pressing the paddle keys
+//! - `paddle <left|right> <y>`: put a paddle at an absolute position
+//! - `key <char>`: inject a key press, e.g. `key \n` to pick a menu item
+//! - `pause` / `resume`: stop or restart the simulation on the timer
+//! - `step [n]`: advance the simulation n frames (default 1), normally while paused
+//! - `seed <n>`: restart the random sequence, so the same seed and inputs replay a match
+//! - `exit`: back to the interactive shell
+
+use core::fmt::{self, Write};
+use kernel::serial::Serial;
+use pc_keyboard::DecodedKey;
+use pong::PongGame;
+use crate::{handle_keyboard_input, screen, with_game};
+
+/// Most frames a single `step` may advance.
+const MAX_STEPS: u32 = 10_000;
+
+/// Whether the shell should stay in remote mode after a request.
+pub enum Session {
+    Continue,
+    Exit,
+}
+
+/// Runs one request line and writes its reply.
+pub fn execute(line: &str, out: &mut Serial) -> Session {
+    let mut words = line.split_ascii_whitespace();
+    let result = match words.next() {
+        Some("exit") => return Session::Exit,
+        Some("state") => Ok(()),
+        Some("move") => move_paddle(words.next(), words.next(), words.next()),
+        Some("paddle") => set_paddle(words.next(), words.next()),
+        Some("key") => inject_key(line.trim_start()["key".len()..].trim_start()),
+        Some("pause") => {
+            with_game(|game| game.frozen = true);
+            Ok(())
+        }
+        Some("resume") => {
+            with_game(|game| game.frozen = false);
+            Ok(())
+        }
+        Some("step") => step(words.next()),
+        Some("seed") => reseed(words.next()),
+        Some(_) => Err("unknown request"),
+        None => Err("empty request"),
+    };
+    let _ = match result {
+        Ok(()) => with_game(|game| write_state(game, out)),
+        Err(error) => write!(out, "{{\"ok\":false,\"error\":\"{error}\"}}\r\n"),
+    };
+    Session::Continue
+}
+
+fn side(word: Option<&str>) -> Result<bool, &'static str> {
+    match word {
+        Some("left") => Ok(true),
+        Some("right") => Ok(false),
+        _ => Err("expected left or right"),
+    }
+}
+
+fn count(word: Option<&str>, default: u32) -> Result<u32, &'static str> {
+    match word {
+        Some(word) => word.parse().map_err(|_| "expected a count"),
+        None => Ok(default),
+    }
+}
+
+fn move_paddle(which: Option<&str>, direction: Option<&str>, steps: Option<&str>) -> Result<(), &'static str> {
+    let left = side(which)?;
+    let up = match direction {
+        Some("up") => true,
+        Some("down") => false,
+        _ => return Err("expected up or down"),
+    };
+    let steps = count(steps, 1)?.min(MAX_STEPS);
+    with_game(|game| {
+        for _ in 0..steps {
+            if left {
+                game.move_left_paddle(up);
+            } else {
+                game.move_right_paddle(up);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn set_paddle(which: Option<&str>, y: Option<&str>) -> Result<(), &'static str> {
+    let left = side(which)?;
+    let y: isize = y.and_then(|y| y.parse().ok()).ok_or("expected a y position")?;
+    with_game(|game| {
+        let y = y.clamp(0, (game.height - game.paddle_height) as isize);
+        if left {
+            game.left_paddle = y;
+        } else {
+            game.right_paddle = y;
+        }
+    });
+    Ok(())
+}
+
+fn inject_key(key: &str) -> Result<(), &'static str> {
+    let c = match key {
+        "\\n" => '\n',
+        key => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err("expected a single character"),
+            }
+        }
+    };
+    x86_64::instructions::interrupts::without_interrupts(|| handle_keyboard_input(DecodedKey::Unicode(c)));
+    Ok(())
+}
+
+fn reseed(seed: Option<&str>) -> Result<(), &'static str> {
+    let seed = seed.and_then(|seed| seed.parse().ok()).ok_or("expected a seed")?;
+    with_game(|game| game.reseed(seed));
+    Ok(())
+}
+
+fn step(frames: Option<&str>) -> Result<(), &'static str> {
+    let frames = count(frames, 1)?.min(MAX_STEPS);
+    with_game(|game| {
+        for _ in 0..frames {
+            game.update();
+        }
+        game.draw(screen::screenwriter());
+    });
+    Ok(())
+}
+
+fn write_state(game: &PongGame, out: &mut Serial) -> fmt::Result {
+    write!(out, "{{\"ok\":true,\"tick\":{},\"mode\":\"{:?}\",\"players\":\"{:?}\",\"paused\":{},\"seed\":{},",
+        kernel::ticks(), game.game_mode, game.players, game.frozen, game.seed())?;
+    write!(out, "\"ball\":{{\"x\":{},\"y\":{},\"dx\":{},\"dy\":{},\"size\":{}}},",
+        game.ball_x.round(), game.ball_y.round(), game.ball_dx.round(), game.ball_dy.round(), game.ball_size)?;
+    write!(out, "\"paddles\":{{\"left\":{},\"right\":{},\"width\":{},\"height\":{}}},",
+        game.left_paddle, game.right_paddle, game.paddle_width, game.paddle_height)?;
+    write!(out, "\"score\":{{\"left\":{},\"right\":{}}},", game.left_score, game.right_score)?;
+    write!(out, "\"sets\":{{\"left\":{},\"right\":{}}},", game.left_sets, game.right_sets)?;
+    write!(out, "\"field\":{{\"width\":{},\"height\":{}}}}}\r\n", game.width, game.height)
+}
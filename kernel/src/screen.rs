@@ -1,8 +1,16 @@
+use alloc::vec;
+use alloc::vec::Vec;
 use core::{fmt, ptr};
-use noto_sans_mono_bitmap::{FontWeight, get_raster, RasterizedChar};
+use noto_sans_mono_bitmap::{get_raster, get_raster_width, RasterizedChar};
+use console::Console;
 use bootloader_api::info::{FrameBuffer, FrameBufferInfo, PixelFormat};
-use noto_sans_mono_bitmap::RasterHeight::Size16;
+pub use noto_sans_mono_bitmap::{FontWeight, RasterHeight};
 use kernel::RacyCell;
+use pong::block_font::{self, ADVANCE, GLYPH_WIDTH};
+use pong::render::{Renderer, TextStyle};
+pub use pong::render::{Color, Rect};
+
+mod console;
 
 static WRITER: RacyCell<Option<ScreenWriter>> = RacyCell::new(None);
 pub struct Writer;
@@ -27,136 +35,584 @@ pub fn init(buffer: &'static mut FrameBuffer) {
 
 const LINE_SPACING: usize = 0;
 
+/// Once a frame has more dirty rectangles than this, they are collapsed into their bounding box.
+const MAX_DIRTY_RECTS: usize = 16;
+
+/// A colour already converted to the framebuffer's native format with `ScreenWriter::pixel`.
+/// Only the first `bytes_per_pixel()` bytes are meaningful.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pixel([u8; 4]);
+
+/// Anything the drawing functions accept as a colour. Passing a `Pixel` skips the conversion,
+/// which is what cached palettes are for.
+pub trait Paint: Copy {
+    fn to_pixel(self, writer: &ScreenWriter) -> Pixel;
+}
+
+impl Paint for Color {
+    fn to_pixel(self, writer: &ScreenWriter) -> Pixel {
+        writer.pixel(self)
+    }
+}
+
+impl Paint for Pixel {
+    fn to_pixel(self, _writer: &ScreenWriter) -> Pixel {
+        self
+    }
+}
+
+/// A size and weight of the Noto Sans Mono bitmap font. Sizes are the raster heights the
+/// `noto-sans-mono-bitmap` crate ships (16, 20, 24 and 32 pixels).
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    pub weight: FontWeight,
+    pub size: RasterHeight,
+}
+
+impl Font {
+    pub const REGULAR: Font = Font::new(FontWeight::Regular, RasterHeight::Size16);
+    pub const BOLD: Font = Font::new(FontWeight::Bold, RasterHeight::Size16);
+    pub const HEADING: Font = Font::new(FontWeight::Bold, RasterHeight::Size24);
+    pub const TITLE: Font = Font::new(FontWeight::Bold, RasterHeight::Size32);
+
+    pub const fn new(weight: FontWeight, size: RasterHeight) -> Self {
+        Font { weight, size }
+    }
+
+    pub const fn height(&self) -> usize {
+        self.size.val()
+    }
+
+    fn raster(&self, c: char) -> Option<RasterizedChar> {
+        get_raster(c, self.weight, self.size)
+    }
+
+    /// Horizontal advance of `c`. Characters missing from the font advance like a space.
+    pub fn char_width(&self, c: char) -> usize {
+        self.raster(c).map_or(get_raster_width(self.weight, self.size), |raster| raster.width())
+    }
+
+    pub fn text_width(&self, text: &str) -> usize {
+        text.chars().map(|c| self.char_width(c)).sum()
+    }
+}
+
+/// Position and width of one colour channel inside a packed pixel.
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    shift: u8,
+    bits: u8,
+}
+
+impl Channel {
+    fn encode(&self, value: u8) -> u32 {
+        ((value >> (8 - self.bits)) as u32) << self.shift
+    }
+
+    fn decode(&self, pixel: u32) -> u8 {
+        let max = (1u32 << self.bits) - 1;
+        (((pixel >> self.shift) & max) * 255 / max) as u8
+    }
+}
+
+/// How a colour is laid out in the framebuffer's bytes, worked out once from `FrameBufferInfo`.
+#[derive(Debug, Clone, Copy)]
+enum PixelLayout {
+    Rgb,
+    Bgr,
+    Gray,
+    /// Little-endian pixel value with each channel at an arbitrary bit offset,
+    /// e.g. RGB565 on 2 bytes per pixel.
+    Bitmask {
+        red: Channel,
+        green: Channel,
+        blue: Channel,
+    },
+}
+
+impl PixelLayout {
+    fn new(info: &FrameBufferInfo) -> Self {
+        match info.pixel_format {
+            PixelFormat::Rgb => PixelLayout::Rgb,
+            PixelFormat::Bgr => PixelLayout::Bgr,
+            PixelFormat::U8 => PixelLayout::Gray,
+            PixelFormat::Unknown { red_position, green_position, blue_position } => {
+                let total_bits = (info.bytes_per_pixel * 8).min(32) as u8;
+                let positions = [red_position, green_position, blue_position];
+                // The firmware only reports where each channel starts, so a channel is assumed to
+                // run up to the next one. The top channel gets no more bits than the widest of the
+                // others, which leaves the padding bit of e.g. RGB555 unused.
+                let mut bits = positions.map(|position| {
+                    positions.iter()
+                        .filter(|&&other| other > position)
+                        .min()
+                        .map_or(0, |&next| (next - position).min(8))
+                });
+                let widest = bits.iter().copied().max().unwrap_or(8).max(1);
+                for (channel_bits, &position) in bits.iter_mut().zip(positions.iter()) {
+                    if *channel_bits == 0 {
+                        *channel_bits = total_bits.saturating_sub(position).min(widest).clamp(1, 8);
+                    }
+                }
+                PixelLayout::Bitmask {
+                    red: Channel { shift: red_position, bits: bits[0] },
+                    green: Channel { shift: green_position, bits: bits[1] },
+                    blue: Channel { shift: blue_position, bits: bits[2] },
+                }
+            }
+            // `PixelFormat` is non-exhaustive; BGR is what GOP firmware almost always reports
+            _ => PixelLayout::Bgr,
+        }
+    }
+
+    fn pack(&self, Color { r, g, b }: Color) -> Pixel {
+        let bytes = match self {
+            PixelLayout::Rgb => [r, g, b, 0],
+            PixelLayout::Bgr => [b, g, r, 0],
+            PixelLayout::Gray => {
+                // ITU-R BT.601 luma weights, scaled to 256
+                let luma = (r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8;
+                [luma as u8, 0, 0, 0]
+            }
+            PixelLayout::Bitmask { red, green, blue } => {
+                (red.encode(r) | green.encode(g) | blue.encode(b)).to_le_bytes()
+            }
+        };
+        Pixel(bytes)
+    }
+
+    fn unpack(&self, Pixel(bytes): Pixel) -> Color {
+        match self {
+            PixelLayout::Rgb => Color::rgb(bytes[0], bytes[1], bytes[2]),
+            PixelLayout::Bgr => Color::rgb(bytes[2], bytes[1], bytes[0]),
+            PixelLayout::Gray => Color::rgb(bytes[0], bytes[0], bytes[0]),
+            PixelLayout::Bitmask { red, green, blue } => {
+                let value = u32::from_le_bytes(bytes);
+                Color::rgb(red.decode(value), green.decode(value), blue.decode(value))
+            }
+        }
+    }
+}
+
+/// Draws into a heap-allocated back buffer and copies it to the real framebuffer on `present()`.
+///
+/// Every drawing call records the rectangle it touched, and `present()` copies only those
+/// rectangles, so a frame that moves a ball and two paddles costs a few small copies instead
+/// of the whole screen. Drawing can be restricted to a clip rectangle with `set_clip`, which
+/// lets callers repaint exactly the damaged parts of a scene.
 pub struct ScreenWriter {
     framebuffer: &'static mut [u8],
+    back_buffer: Vec<u8>,
     info: FrameBufferInfo,
-    x_pos: usize,
-    y_pos: usize,
+    layout: PixelLayout,
+    console: Console,
+    dirty: Vec<Rect>,
+    clip: Option<Rect>,
+    in_frame: bool,
 }
 
 impl ScreenWriter {
     pub fn new(framebuffer: &'static mut [u8], info: FrameBufferInfo) -> Self {
+        let back_buffer = vec![0; framebuffer.len()];
         let mut logger = Self {
             framebuffer,
+            back_buffer,
             info,
-            x_pos: 0,
-            y_pos: 0,
+            layout: PixelLayout::new(&info),
+            console: Console::new(),
+            dirty: Vec::with_capacity(MAX_DIRTY_RECTS + 1),
+            clip: None,
+            in_frame: false,
         };
         logger.clear();
+        logger.present();
         logger
     }
 
-    fn newline(&mut self) {
-        self.y_pos += Size16 as usize + LINE_SPACING;
-        self.carriage_return()
+    /// Starts composing a frame. Text written through `fmt::Write` is no longer presented
+    /// immediately; everything becomes visible at once on the next `present()`.
+    pub fn begin_frame(&mut self) {
+        self.in_frame = true;
     }
 
-    fn carriage_return(&mut self) {
-        self.x_pos = 0;
+    /// Copies the dirty rectangles of the back buffer to the framebuffer and ends the current frame.
+    pub fn present(&mut self) {
+        self.in_frame = false;
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let row_bytes = self.info.stride * bytes_per_pixel;
+
+        for rect in self.dirty.drain(..) {
+            if rect.x == 0 && rect.width == self.info.width {
+                // Full-width rectangles are one contiguous block
+                let start = rect.y * row_bytes;
+                let end = (rect.bottom() * row_bytes).min(self.framebuffer.len());
+                self.framebuffer[start..end].copy_from_slice(&self.back_buffer[start..end]);
+                continue;
+            }
+            for y in rect.y..rect.bottom() {
+                let start = y * row_bytes + rect.x * bytes_per_pixel;
+                let end = start + rect.width * bytes_per_pixel;
+                self.framebuffer[start..end].copy_from_slice(&self.back_buffer[start..end]);
+            }
+        }
+    }
+
+    /// Records that `rect` (already clipped to the screen) changed since the last `present()`.
+    /// Touching rectangles are merged so the list stays short.
+    fn mark_dirty(&mut self, rect: Rect) {
+        if rect.is_empty() || self.dirty.iter().any(|d| d.intersection(&rect) == Some(rect)) {
+            return;
+        }
+
+        let mut merged = rect;
+        while let Some(i) = self.dirty.iter().position(|d| d.touches(&merged)) {
+            merged = merged.union(&self.dirty.swap_remove(i));
+        }
+        self.dirty.push(merged);
+
+        if self.dirty.len() > MAX_DIRTY_RECTS {
+            let bounds = self.dirty.iter().fold(merged, |acc, d| acc.union(d));
+            self.dirty.clear();
+            self.dirty.push(bounds);
+        }
     }
 
+    /// Restricts all drawing to `clip` until it is reset with `set_clip(None)`.
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    /// Marks a region as changed without drawing to it, e.g. after the whole frame was
+    /// redrawn outside of the usual primitives.
+    #[allow(dead_code)]
+    pub fn invalidate(&mut self, rect: Rect) {
+        if let Some(visible) = self.visible(rect) {
+            self.mark_dirty(visible);
+        }
+    }
+
+    /// The part of `rect` that lies on screen and inside the clip rectangle.
+    fn visible(&self, rect: Rect) -> Option<Rect> {
+        let screen = Rect::new(0, 0, self.width(), self.height());
+        let on_screen = rect.intersection(&screen)?;
+        match self.clip {
+            Some(clip) => on_screen.intersection(&clip),
+            None => Some(on_screen),
+        }
+    }
+
+    /// Clears the screen to the console background and moves the console cursor home.
     pub fn clear(&mut self) {
-        self.x_pos = 0;
-        self.y_pos = 0;
-        self.framebuffer.fill(0);
+        let background = self.console_background();
+        self.clear_screen(background);
+        self.home_cursor();
+    }
+
+    /// Moves the whole frame up by `lines` pixel rows, filling the uncovered band at the
+    /// bottom with `color`.
+    pub fn scroll_up(&mut self, lines: usize, color: impl Paint) {
+        let (width, height) = (self.width(), self.height());
+        let lines = lines.min(height);
+        let row_bytes = self.info.stride * self.info.bytes_per_pixel;
+        self.back_buffer.copy_within(lines * row_bytes..height * row_bytes, 0);
+        self.mark_dirty(Rect::new(0, 0, width, height));
+        self.fill_rect(0, height - lines, width, lines, color);
+    }
+
+    pub fn clear_screen(&mut self, color: impl Paint) {
+        let (width, height) = (self.width(), self.height());
+        self.fill_rect(0, 0, width, height, color);
+    }
+
+    /// Fills a rectangle, clipped to the screen. The pixel value is packed once and every row
+    /// is written with whole-pixel stores.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: impl Paint) {
+        let Some(rect) = self.visible(Rect::new(x, y, width, height)) else {
+            return;
+        };
+
+        let pixel = color.to_pixel(self);
+        for row in rect.y..rect.bottom() {
+            self.fill_span(row, rect.x, rect.width, pixel);
+        }
+        self.mark_dirty(rect);
+    }
+
+    #[allow(dead_code)]
+    pub fn draw_hline(&mut self, x: usize, y: usize, length: usize, color: impl Paint) {
+        self.fill_rect(x, y, length, 1, color);
     }
 
-    pub fn clear_screen(&mut self, r: u8, g: u8, b: u8) {
-        for y in 0..self.height() {
-            for x in 0..self.width() {
-                self.safe_draw_pixel(x, y, r, g, b);
+    pub fn draw_vline(&mut self, x: usize, y: usize, length: usize, color: impl Paint) {
+        self.fill_rect(x, y, 1, length, color);
+    }
+
+    /// Copies a `width` x `height` image to (`x`, `y`), clipped to the screen. `pixels` must
+    /// already be in the framebuffer's native format, `bytes_per_pixel()` bytes per pixel; a
+    /// shorter buffer draws nothing.
+    #[allow(dead_code)]
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, height: usize, pixels: &[u8]) {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        if pixels.len() < width.saturating_mul(height).saturating_mul(bytes_per_pixel) {
+            return;
+        }
+        let Some(rect) = self.visible(Rect::new(x, y, width, height)) else {
+            return;
+        };
+
+        let row_bytes = rect.width * bytes_per_pixel;
+        for row in rect.y..rect.bottom() {
+            let src = ((row - y) * width + (rect.x - x)) * bytes_per_pixel;
+            let dst = (row * self.info.stride + rect.x) * bytes_per_pixel;
+            self.back_buffer[dst..dst + row_bytes].copy_from_slice(&pixels[src..src + row_bytes]);
+        }
+        self.mark_dirty(rect);
+    }
+
+    #[allow(dead_code)]
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.info.bytes_per_pixel
+    }
+
+    /// Converts a colour to the framebuffer's native pixel format.
+    pub fn pixel(&self, color: Color) -> Pixel {
+        self.layout.pack(color)
+    }
+
+    /// Writes `length` copies of `pixel` starting at (`x`, `y`). Does not clip or mark rows dirty.
+    fn fill_span(&mut self, y: usize, x: usize, length: usize, Pixel(pixel): Pixel) {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        let start = (y * self.info.stride + x) * bytes_per_pixel;
+        let span = &mut self.back_buffer[start..start + length * bytes_per_pixel];
+
+        if bytes_per_pixel == 4 {
+            let value = u32::from_ne_bytes(pixel);
+            for dst in span.chunks_exact_mut(4) {
+                unsafe { dst.as_mut_ptr().cast::<u32>().write_unaligned(value) };
+            }
+        } else {
+            for dst in span.chunks_exact_mut(bytes_per_pixel) {
+                dst.copy_from_slice(&pixel[..bytes_per_pixel]);
             }
         }
     }
 
     pub fn width(&self) -> usize {
-        self.info.width as usize
+        self.info.width
     }
 
     pub fn height(&self) -> usize {
-        self.info.height as usize
+        self.info.height
     }
 
-    fn write_char(&mut self, c: char) {
-        match c {
-            '\n' => self.newline(),
-            '\r' => self.carriage_return(),
-            c => {
-                if let Some(bitmap_char) = get_raster(c, FontWeight::Regular, Size16) {
-                    if self.x_pos + bitmap_char.width() > self.width() {
-                        self.newline();
-                    }
-                    if self.y_pos + bitmap_char.height() > self.height() {
-                        self.clear();
-                    }
-                    self.write_rendered_char(bitmap_char);
-                }
+    pub fn safe_draw_pixel(&mut self, x: usize, y: usize, color: impl Paint) {
+        let pixel = color.to_pixel(self);
+        if self.put_pixel(x, y, pixel) {
+            self.mark_dirty(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    /// Draws `color` over what is already at (`x`, `y`) with the given opacity, 255 being opaque.
+    #[allow(dead_code)]
+    pub fn draw_pixel_blend(&mut self, x: usize, y: usize, color: Color, alpha: u8) {
+        if self.blend_pixel(x, y, color, alpha) {
+            self.mark_dirty(Rect::new(x, y, 1, 1));
+        }
+    }
+
+    /// Darkens or tints a rectangle by blending `color` over it, e.g. to dim the playfield
+    /// behind an overlay.
+    pub fn fill_rect_blend(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color, alpha: u8) {
+        let Some(rect) = self.visible(Rect::new(x, y, width, height)) else {
+            return;
+        };
+        for row in rect.y..rect.bottom() {
+            for column in rect.x..rect.right() {
+                self.blend_pixel(column, row, color, alpha);
             }
         }
+        self.mark_dirty(rect);
+    }
+
+    /// Reads back a pixel of the current frame.
+    pub fn read_pixel(&self, x: usize, y: usize) -> Color {
+        let offset = self.pixel_offset(x, y);
+        let mut bytes = [0; 4];
+        let bytes_per_pixel = self.info.bytes_per_pixel.min(4);
+        bytes[..bytes_per_pixel].copy_from_slice(&self.back_buffer[offset..offset + bytes_per_pixel]);
+        self.layout.unpack(Pixel(bytes))
+    }
+
+    fn pixel_offset(&self, x: usize, y: usize) -> usize {
+        (y * self.info.stride + x) * self.info.bytes_per_pixel
     }
 
-    pub fn safe_draw_pixel(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
+    /// Writes one pixel if it is on screen and inside the clip rectangle, without marking it
+    /// dirty. Returns whether anything was written.
+    fn put_pixel(&mut self, x: usize, y: usize, Pixel(color): Pixel) -> bool {
         if x >= self.width() || y >= self.height() {
-            return;
+            return false;
+        }
+        if self.clip.is_some_and(|clip| !clip.contains(x, y)) {
+            return false;
         }
         
-        let pixel_offset = y * self.info.stride as usize + x;
-        let color = match self.info.pixel_format {
-            PixelFormat::Rgb => [r, g, b, 0],
-            PixelFormat::Bgr => [b, g, r, 0],
-            other => {
-                self.info.pixel_format = PixelFormat::Rgb;
-                panic!("pixel format {:?} not supported", other)
-            }
-        };
-        
-        let bytes_per_pixel = self.info.bytes_per_pixel as usize;
-        let byte_offset = pixel_offset * bytes_per_pixel;
+        let byte_offset = self.pixel_offset(x, y);
+        let bytes_per_pixel = self.info.bytes_per_pixel;
         
-        if byte_offset + bytes_per_pixel <= self.framebuffer.len() {
-            self.framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]
+        if byte_offset + bytes_per_pixel <= self.back_buffer.len() {
+            self.back_buffer[byte_offset..(byte_offset + bytes_per_pixel)]
                 .copy_from_slice(&color[..bytes_per_pixel]);
+            return true;
         }
+        false
     }
 
-    pub fn draw_char(&mut self, x: usize, y: usize, c: char, r: u8, g: u8, b: u8) {
-        if let Some(bitmap_char) = get_raster(c, FontWeight::Regular, Size16) {
-            for (char_y, row) in bitmap_char.raster().iter().enumerate() {
-                for (char_x, &intensity) in row.iter().enumerate() {
-                    if intensity > 0 {
-                        self.safe_draw_pixel(x + char_x, y + char_y, r, g, b);
-                    }
+    /// Like `put_pixel`, but mixes `color` with the pixel underneath.
+    fn blend_pixel(&mut self, x: usize, y: usize, color: Color, alpha: u8) -> bool {
+        match alpha {
+            0 => false,
+            255 => self.put_pixel(x, y, self.pixel(color)),
+            _ if x >= self.width() || y >= self.height() => false,
+            _ => {
+                let blended = color.blend_over(self.read_pixel(x, y), alpha);
+                self.put_pixel(x, y, self.pixel(blended))
+            }
+        }
+    }
+
+    /// Draws a glyph anti-aliased: the font's intensity for each pixel is used as its opacity.
+    /// Returns how far the next character should be drawn to the right.
+    pub fn draw_char(&mut self, x: usize, y: usize, c: char, font: Font, color: impl Paint) -> usize {
+        let pixel = color.to_pixel(self);
+        let color = self.layout.unpack(pixel);
+        let Some(bitmap_char) = font.raster(c) else {
+            return font.char_width(c);
+        };
+
+        for (char_y, row) in bitmap_char.raster().iter().enumerate() {
+            for (char_x, &intensity) in row.iter().enumerate() {
+                if intensity == 255 {
+                    self.put_pixel(x + char_x, y + char_y, pixel);
+                } else {
+                    self.blend_pixel(x + char_x, y + char_y, color, intensity);
                 }
             }
         }
+        if let Some(glyph) = self.visible(Rect::new(x, y, bitmap_char.width(), bitmap_char.height())) {
+            self.mark_dirty(glyph);
+        }
+        bitmap_char.width()
     }
 
-    pub fn draw_string(&mut self, x: usize, y: usize, text: &str, r: u8, g: u8, b: u8) {
+    pub fn draw_string(&mut self, x: usize, y: usize, text: &str, font: Font, color: impl Paint) {
+        let pixel = color.to_pixel(self);
         let mut x_pos = x;
         for c in text.chars() {
-            self.draw_char(x_pos, y, c, r, g, b);
-            x_pos += 8;
+            x_pos += self.draw_char(x_pos, y, c, font, pixel);
         }
     }
 
-    pub fn draw_string_centered(&mut self, y: usize, text: &str, r: u8, g: u8, b: u8) {
-        let x = (self.width() - text.len() * 8) / 2;
-        self.draw_string(x, y, text, r, g, b);
+    pub fn draw_string_centered(&mut self, y: usize, text: &str, font: Font, color: impl Paint) {
+        let x = self.width().saturating_sub(font.text_width(text)) / 2;
+        self.draw_string(x, y, text, font, color);
     }
 
-    fn write_rendered_char(&mut self, rendered_char: RasterizedChar) {
-        for (y, row) in rendered_char.raster().iter().enumerate() {
-            for (x, &byte) in row.iter().enumerate() {
-                self.safe_draw_pixel(
-                    self.x_pos + x, 
-                    self.y_pos + y,
-                    byte / 4,
-                    byte,
-                    byte / 2
-                );
+    /// Draws `text` in the 5x7 block font, every font pixel being a `scale` x `scale` square.
+    /// Characters the block font lacks leave a blank cell.
+    pub fn draw_block_text(&mut self, x: usize, y: usize, text: &str, scale: usize, color: impl Paint) {
+        let pixel = color.to_pixel(self);
+        let mut x_pos = x;
+        for c in text.chars() {
+            if let Some(rows) = block_font::glyph(c) {
+                for (row, bits) in rows.iter().enumerate() {
+                    let is_set = |column: usize| bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0;
+                    // Each horizontal run of set pixels becomes a single rectangle
+                    let mut column = 0;
+                    while column < GLYPH_WIDTH {
+                        if !is_set(column) {
+                            column += 1;
+                            continue;
+                        }
+                        let start = column;
+                        while column < GLYPH_WIDTH && is_set(column) {
+                            column += 1;
+                        }
+                        self.fill_rect(x_pos + start * scale, y + row * scale, (column - start) * scale, scale, pixel);
+                    }
+                }
             }
+            x_pos += ADVANCE * scale;
         }
-        self.x_pos += rendered_char.width();
+    }
+
+    pub fn draw_block_text_centered(&mut self, y: usize, text: &str, scale: usize, color: impl Paint) {
+        let x = self.width().saturating_sub(block_font::text_width(text, scale)) / 2;
+        self.draw_block_text(x, y, text, scale, color);
+    }
+
+}
+
+impl From<TextStyle> for Font {
+    fn from(style: TextStyle) -> Font {
+        match style {
+            TextStyle::Regular => Font::REGULAR,
+            TextStyle::Bold => Font::BOLD,
+            TextStyle::Heading => Font::HEADING,
+            TextStyle::Title => Font::TITLE,
+        }
+    }
+}
+
+impl Renderer for ScreenWriter {
+    type Pixel = Pixel;
+
+    fn pixel(&self, color: Color) -> Pixel {
+        ScreenWriter::pixel(self, color)
+    }
+
+    fn begin_frame(&mut self) {
+        ScreenWriter::begin_frame(self);
+    }
+
+    fn present(&mut self) {
+        ScreenWriter::present(self);
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        ScreenWriter::set_clip(self, clip);
+    }
+
+    fn clear_screen(&mut self, color: Pixel) {
+        ScreenWriter::clear_screen(self, color);
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Pixel) {
+        ScreenWriter::fill_rect(self, x, y, width, height, color);
+    }
+
+    fn fill_rect_blend(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color, alpha: u8) {
+        ScreenWriter::fill_rect_blend(self, x, y, width, height, color, alpha);
+    }
+
+    fn draw_vline(&mut self, x: usize, y: usize, length: usize, color: Pixel) {
+        ScreenWriter::draw_vline(self, x, y, length, color);
+    }
+
+    fn draw_string(&mut self, x: usize, y: usize, text: &str, style: TextStyle, color: Pixel) {
+        ScreenWriter::draw_string(self, x, y, text, style.into(), color);
+    }
+
+    fn draw_string_centered(&mut self, y: usize, text: &str, style: TextStyle, color: Pixel) {
+        ScreenWriter::draw_string_centered(self, y, text, style.into(), color);
+    }
+
+    fn draw_block_text(&mut self, x: usize, y: usize, text: &str, scale: usize, color: Pixel) {
+        ScreenWriter::draw_block_text(self, x, y, text, scale, color);
+    }
+
+    fn draw_block_text_centered(&mut self, y: usize, text: &str, scale: usize, color: Pixel) {
+        ScreenWriter::draw_block_text_centered(self, y, text, scale, color);
     }
 }
 
@@ -165,8 +621,13 @@ unsafe impl Sync for ScreenWriter {}
 
 impl fmt::Write for ScreenWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.draw_cursor(false);
         for c in s.chars() {
-            self.write_char(c);
+            self.console_char(c);
+        }
+        self.draw_cursor(true);
+        if !self.in_frame {
+            self.present();
         }
         Ok(())
     }
@@ -0,0 +1,301 @@
+//! Text console drawn through `ScreenWriter`: a character grid with a cursor, scrolling, tab
+//! stops, backspace and the commonly used subset of ANSI/VT100 escape sequences:
+//!
+//! - `ESC[<n>A`/`B`/`C`/`D` cursor up/down/forward/back, `ESC[<row>;<col>H` (or `f`) position
+//! - `ESC[<n>J` erase display, `ESC[<n>K` erase line (0: to end, 1: to start, 2: all)
+//! - `ESC[...m` bold, normal, 30-37/90-97 foreground, 40-47/100-107 background, 39/49 defaults
+//! - `ESC[s`/`u` save/restore cursor, `ESC[?25l`/`h` hide/show cursor
+
+use super::{Color, Font, FontWeight, Rect, ScreenWriter};
+use noto_sans_mono_bitmap::get_raster_width;
+
+const TAB_WIDTH: usize = 8;
+const MAX_PARAMS: usize = 4;
+const CURSOR_HEIGHT: usize = 2;
+/// Widest cell whose pixels under the cursor can be saved; wider cells keep only this much.
+const MAX_CURSOR_WIDTH: usize = 32;
+const DEFAULT_FOREGROUND: Color = Color::LIGHT_GRAY;
+const DEFAULT_BACKGROUND: Color = Color::BLACK;
+
+/// The 16 colours selected by SGR 30-37 and 90-97 (and the matching backgrounds).
+const ANSI_COLORS: [Color; 16] = [
+    Color::rgb(0x00, 0x00, 0x00),
+    Color::rgb(0xaa, 0x00, 0x00),
+    Color::rgb(0x00, 0xaa, 0x00),
+    Color::rgb(0xaa, 0x55, 0x00),
+    Color::rgb(0x00, 0x00, 0xaa),
+    Color::rgb(0xaa, 0x00, 0xaa),
+    Color::rgb(0x00, 0xaa, 0xaa),
+    Color::rgb(0xaa, 0xaa, 0xaa),
+    Color::rgb(0x55, 0x55, 0x55),
+    Color::rgb(0xff, 0x55, 0x55),
+    Color::rgb(0x55, 0xff, 0x55),
+    Color::rgb(0xff, 0xff, 0x55),
+    Color::rgb(0x55, 0x55, 0xff),
+    Color::rgb(0xff, 0x55, 0xff),
+    Color::rgb(0x55, 0xff, 0xff),
+    Color::rgb(0xff, 0xff, 0xff),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    /// Saw `ESC`, waiting for `[`.
+    Start,
+    /// Inside a control sequence, collecting parameters.
+    Csi,
+}
+
+pub(super) struct Console {
+    column: usize,
+    row: usize,
+    saved: (usize, usize),
+    foreground: Color,
+    background: Color,
+    bold: bool,
+    cursor_visible: bool,
+    /// Top-left corner of the cursor on screen, if it's drawn, and the pixels it covers.
+    cursor_at: Option<(usize, usize)>,
+    under_cursor: [[Color; MAX_CURSOR_WIDTH]; CURSOR_HEIGHT],
+    escape: Escape,
+    params: [usize; MAX_PARAMS],
+    param_count: usize,
+    private: bool,
+}
+
+impl Console {
+    pub(super) const fn new() -> Self {
+        Console {
+            column: 0,
+            row: 0,
+            saved: (0, 0),
+            foreground: DEFAULT_FOREGROUND,
+            background: DEFAULT_BACKGROUND,
+            bold: false,
+            cursor_visible: true,
+            cursor_at: None,
+            under_cursor: [[DEFAULT_BACKGROUND; MAX_CURSOR_WIDTH]; CURSOR_HEIGHT],
+            escape: Escape::None,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            private: false,
+        }
+    }
+
+    fn font(&self) -> Font {
+        Font::new(if self.bold { FontWeight::Bold } else { FontWeight::Regular }, Font::REGULAR.size)
+    }
+
+    /// Parameter `index` of the current escape sequence, or `default` if it was omitted or 0.
+    fn param(&self, index: usize, default: usize) -> usize {
+        match self.params[index] {
+            0 => default,
+            value => value,
+        }
+    }
+}
+
+impl ScreenWriter {
+    fn cell_width(&self) -> usize {
+        get_raster_width(FontWeight::Regular, Font::REGULAR.size)
+    }
+
+    fn cell_height(&self) -> usize {
+        Font::REGULAR.height() + super::LINE_SPACING
+    }
+
+    pub fn columns(&self) -> usize {
+        self.width() / self.cell_width()
+    }
+
+    pub fn rows(&self) -> usize {
+        self.height() / self.cell_height()
+    }
+
+    fn cell_rect(&self, column: usize, row: usize, columns: usize) -> Rect {
+        Rect::new(column * self.cell_width(), row * self.cell_height(), columns * self.cell_width(), self.cell_height())
+    }
+
+    /// Feeds one character to the console, interpreting control characters and escapes.
+    pub(super) fn console_char(&mut self, c: char) {
+        match self.console.escape {
+            Escape::Start => {
+                self.console.escape = if c == '[' { Escape::Csi } else { Escape::None };
+                self.console.params = [0; MAX_PARAMS];
+                self.console.param_count = 0;
+                self.console.private = false;
+            }
+            Escape::Csi => self.escape_char(c),
+            Escape::None => match c {
+                '\x1b' => self.console.escape = Escape::Start,
+                '\n' => self.newline(),
+                '\r' => self.console.column = 0,
+                '\t' => {
+                    let next_stop = (self.console.column / TAB_WIDTH + 1) * TAB_WIDTH;
+                    self.console.column = next_stop.min(self.columns() - 1);
+                }
+                '\x08' => self.console.column = self.console.column.saturating_sub(1),
+                c if c.is_control() => {}
+                c => self.put_char(c),
+            },
+        }
+    }
+
+    fn escape_char(&mut self, c: char) {
+        let console = &mut self.console;
+        match c {
+            '0'..='9' => {
+                let index = console.param_count.min(MAX_PARAMS - 1);
+                let digit = c as usize - '0' as usize;
+                console.params[index] = console.params[index].saturating_mul(10).saturating_add(digit);
+                return;
+            }
+            ';' => {
+                console.param_count += 1;
+                return;
+            }
+            '?' => {
+                console.private = true;
+                return;
+            }
+            _ => {}
+        }
+        console.param_count += 1;
+        console.escape = Escape::None;
+
+        let (rows, columns) = (self.rows(), self.columns());
+        match c {
+            'A' => self.console.row = self.console.row.saturating_sub(self.console.param(0, 1)),
+            'B' => self.console.row = (self.console.row + self.console.param(0, 1)).min(rows - 1),
+            'C' => self.console.column = (self.console.column + self.console.param(0, 1)).min(columns - 1),
+            'D' => self.console.column = self.console.column.saturating_sub(self.console.param(0, 1)),
+            'H' | 'f' => {
+                self.console.row = (self.console.param(0, 1) - 1).min(rows - 1);
+                self.console.column = (self.console.param(1, 1) - 1).min(columns - 1);
+            }
+            'J' => self.erase_display(self.console.params[0]),
+            'K' => self.erase_line(self.console.params[0]),
+            'm' => self.select_graphic_rendition(),
+            's' => self.console.saved = (self.console.column, self.console.row),
+            'u' => (self.console.column, self.console.row) = self.console.saved,
+            'h' | 'l' if self.console.private && self.console.params[0] == 25 => {
+                self.console.cursor_visible = c == 'h';
+            }
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        let console = &mut self.console;
+        for &param in &console.params[..console.param_count.min(MAX_PARAMS)] {
+            match param {
+                0 => {
+                    console.foreground = DEFAULT_FOREGROUND;
+                    console.background = DEFAULT_BACKGROUND;
+                    console.bold = false;
+                }
+                1 => console.bold = true,
+                22 => console.bold = false,
+                30..=37 => console.foreground = ANSI_COLORS[param - 30],
+                90..=97 => console.foreground = ANSI_COLORS[param - 90 + 8],
+                39 => console.foreground = DEFAULT_FOREGROUND,
+                40..=47 => console.background = ANSI_COLORS[param - 40],
+                100..=107 => console.background = ANSI_COLORS[param - 100 + 8],
+                49 => console.background = DEFAULT_BACKGROUND,
+                _ => {}
+            }
+        }
+    }
+
+    /// 0: cursor to end of screen, 1: start of screen to cursor, 2: whole screen.
+    fn erase_display(&mut self, mode: usize) {
+        let row = self.console.row;
+        let background = self.console.background;
+        let (top, bottom) = match mode {
+            0 => (row + 1, self.rows()),
+            1 => (0, row),
+            _ => (0, self.rows()),
+        };
+        if top < bottom {
+            let first = self.cell_rect(0, top, 0);
+            self.fill_rect(0, first.y, self.width(), (bottom - top) * self.cell_height(), background);
+        }
+        if mode != 2 {
+            self.erase_line(mode);
+        }
+    }
+
+    /// 0: cursor to end of line, 1: start of line to cursor, 2: whole line.
+    fn erase_line(&mut self, mode: usize) {
+        let (column, row, columns) = (self.console.column, self.console.row, self.columns());
+        let (start, end) = match mode {
+            0 => (column, columns),
+            1 => (0, column + 1),
+            _ => (0, columns),
+        };
+        let area = self.cell_rect(start, row, end.saturating_sub(start));
+        self.fill_rect(area.x, area.y, area.width, area.height, self.console.background);
+    }
+
+    fn newline(&mut self) {
+        self.console.column = 0;
+        if self.console.row + 1 < self.rows() {
+            self.console.row += 1;
+        } else {
+            let (lines, background) = (self.cell_height(), self.console.background);
+            self.scroll_up(lines, background);
+        }
+    }
+
+    fn put_char(&mut self, c: char) {
+        if self.console.column >= self.columns() {
+            self.newline();
+        }
+        let cell = self.cell_rect(self.console.column, self.console.row, 1);
+        let (font, foreground) = (self.console.font(), self.console.foreground);
+        self.fill_rect(cell.x, cell.y, cell.width, cell.height, self.console.background);
+        self.draw_char(cell.x, cell.y, c, font, foreground);
+        self.console.column += 1;
+    }
+
+    /// Draws (or erases) the underline cursor at the current position. Drawing saves the
+    /// pixels it covers and erasing puts them back, so descenders and underscores survive.
+    pub(super) fn draw_cursor(&mut self, visible: bool) {
+        let width = self.cell_width().min(MAX_CURSOR_WIDTH);
+        if !visible {
+            if let Some((x, y)) = self.console.cursor_at.take() {
+                let pixels = self.console.under_cursor;
+                for (row, colors) in pixels.iter().enumerate() {
+                    for (column, &color) in colors[..width].iter().enumerate() {
+                        self.safe_draw_pixel(x + column, y + row, color);
+                    }
+                }
+            }
+            return;
+        }
+        if !self.console.cursor_visible || self.console.column >= self.columns() {
+            return;
+        }
+        let cell = self.cell_rect(self.console.column, self.console.row, 1);
+        let (x, y) = (cell.x, cell.bottom() - CURSOR_HEIGHT);
+        for row in 0..CURSOR_HEIGHT {
+            for column in 0..width {
+                self.console.under_cursor[row][column] = self.read_pixel(x + column, y + row);
+            }
+        }
+        self.console.cursor_at = Some((x, y));
+        self.fill_rect(x, y, width, CURSOR_HEIGHT, self.console.foreground);
+    }
+
+    /// Moves the console back to the top-left corner, e.g. after the screen was cleared. Any
+    /// saved pixels under the cursor are stale by then.
+    pub(super) fn home_cursor(&mut self) {
+        self.console.column = 0;
+        self.console.row = 0;
+        self.console.cursor_at = None;
+    }
+
+    pub(super) fn console_background(&self) -> Color {
+        self.console.background
+    }
+}
@@ -0,0 +1,324 @@
+//! Interrupt-driven driver for the 16550 UARTs behind COM1-COM4.
+//!
+//! Each port has a transmit and a receive ring buffer. Received bytes are moved into the
+//! receive buffer by the IRQ handler (IRQ4 for COM1/COM3, IRQ3 for COM2/COM4, routed through
+//! the I/O APIC) and read with `read_byte`. Writes are queued and drained by the
+//! transmit-empty interrupt; before interrupts are enabled, or when called from a panic,
+//! bytes are sent by polling instead.
+//!
+//! Ports are set up lazily at `DEFAULT_BAUD` the first time they are used; call `init` to
+//! pick another rate.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+pub const DEFAULT_BAUD: u32 = 115_200;
+const UART_CLOCK: u32 = 115_200;
+const TX_SIZE: usize = 4096;
+const RX_SIZE: usize = 256;
+/// Bytes written per transmit-empty interrupt, the depth of the 16550 FIFO.
+const FIFO_DEPTH: usize = 16;
+
+// Register offsets from the port base
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const INTERRUPT_ID: u16 = 2;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+const SCRATCH: u16 = 7;
+
+// Interrupt enable bits
+const IER_RECEIVED: u8 = 0x01;
+const IER_TRANSMIT_EMPTY: u8 = 0x02;
+// Line status bits
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_TRANSMIT_EMPTY: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComPort {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl ComPort {
+    pub const ALL: [ComPort; 4] = [ComPort::Com1, ComPort::Com2, ComPort::Com3, ComPort::Com4];
+
+    pub fn base(self) -> u16 {
+        match self {
+            ComPort::Com1 => 0x3F8,
+            ComPort::Com2 => 0x2F8,
+            ComPort::Com3 => 0x3E8,
+            ComPort::Com4 => 0x2E8,
+        }
+    }
+
+    /// ISA IRQ line the port raises. COM3 and COM4 share the lines of COM1 and COM2.
+    pub fn irq(self) -> u8 {
+        match self {
+            ComPort::Com1 | ComPort::Com3 => 4,
+            ComPort::Com2 | ComPort::Com4 => 3,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+struct Queue<const N: usize> {
+    data: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Queue<N> {
+    const fn new() -> Self {
+        Queue { data: [0; N], head: 0, len: 0 }
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.data[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+}
+
+pub struct Uart {
+    port: ComPort,
+    baud: u32,
+    initialized: bool,
+    tx: Queue<TX_SIZE>,
+    rx: Queue<RX_SIZE>,
+    /// Bytes dropped because the receive buffer was full.
+    overruns: u64,
+}
+
+static PORTS: [Mutex<Uart>; 4] = [
+    Mutex::new(Uart::new(ComPort::Com1)),
+    Mutex::new(Uart::new(ComPort::Com2)),
+    Mutex::new(Uart::new(ComPort::Com3)),
+    Mutex::new(Uart::new(ComPort::Com4)),
+];
+
+/// Set once the IDT is loaded, after which the transmit-empty interrupt drains the queues.
+static INTERRUPTS_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Runs `f` with exclusive access to a port, initializing it first if needed. Interrupts are
+/// disabled meanwhile so the IRQ handler can't deadlock on the same lock.
+pub fn with_port<R>(port: ComPort, f: impl FnOnce(&mut Uart) -> R) -> R {
+    interrupts::without_interrupts(|| {
+        let mut uart = PORTS[port.index()].lock();
+        if !uart.initialized {
+            uart.configure(DEFAULT_BAUD);
+        }
+        f(&mut uart)
+    })
+}
+
+/// (Re)programs a port for 8N1 at the given baud rate.
+pub fn init(port: ComPort, baud: u32) {
+    with_port(port, |uart| uart.configure(baud));
+}
+
+/// Switches transmission from polling to the interrupt-driven queue.
+pub fn enable_interrupts() {
+    INTERRUPTS_STARTED.store(true, Ordering::Relaxed);
+}
+
+/// Checks that a UART is wired to the port by writing and reading back its scratch register.
+/// Without one, reads of the floating bus return 0xFF.
+pub fn is_present(port: ComPort) -> bool {
+    let mut scratch = register(port.base(), SCRATCH);
+    unsafe {
+        scratch.write(0x5A);
+        scratch.read() == 0x5A
+    }
+}
+
+/// Takes the next received byte, if any.
+pub fn read_byte(port: ComPort) -> Option<u8> {
+    with_port(port, |uart| uart.rx.pop())
+}
+
+/// Whether received bytes are waiting to be read.
+pub fn has_input(port: ComPort) -> bool {
+    with_port(port, |uart| uart.rx.len > 0)
+}
+
+/// Called from the IRQ3/IRQ4 handlers: services every initialized port on that line.
+pub fn handle_interrupt(irq: u8) {
+    for port in ComPort::ALL.into_iter().filter(|port| port.irq() == irq) {
+        // Interrupts are already off inside the handler; never spin on a lock held by the
+        // code we interrupted
+        if let Some(mut uart) = PORTS[port.index()].try_lock()
+            && uart.initialized
+        {
+            uart.service();
+        }
+    }
+}
+
+/// `fmt::Write` handle for a port, as returned by `kernel::serial()`.
+#[derive(Clone, Copy)]
+pub struct Serial(pub ComPort);
+
+impl fmt::Write for Serial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        with_port(self.0, |uart| uart.write_bytes(s.as_bytes()));
+        Ok(())
+    }
+}
+
+/// Writer for the panic handler: polls COM1 directly without taking the port lock, which
+/// the panicking code may be holding.
+pub struct EmergencySerial;
+
+impl fmt::Write for EmergencySerial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let base = ComPort::Com1.base();
+        for &byte in s.as_bytes() {
+            transmit_polling(base, byte);
+        }
+        Ok(())
+    }
+}
+
+fn register(base: u16, offset: u16) -> Port<u8> {
+    Port::new(base + offset)
+}
+
+fn transmit_polling(base: u16, byte: u8) {
+    unsafe {
+        while register(base, LINE_STATUS).read() & LSR_TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        register(base, DATA).write(byte);
+    }
+}
+
+impl Uart {
+    const fn new(port: ComPort) -> Self {
+        Uart {
+            port,
+            baud: DEFAULT_BAUD,
+            initialized: false,
+            tx: Queue::new(),
+            rx: Queue::new(),
+            overruns: 0,
+        }
+    }
+
+    pub fn port(&self) -> ComPort {
+        self.port
+    }
+
+    pub fn baud(&self) -> u32 {
+        self.baud
+    }
+
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+
+    fn configure(&mut self, baud: u32) {
+        let base = self.port.base();
+        if self.initialized {
+            self.flush_polling();
+        }
+        let divisor = (UART_CLOCK / baud.clamp(UART_CLOCK / u16::MAX as u32 + 1, UART_CLOCK)) as u16;
+        unsafe {
+            register(base, INTERRUPT_ENABLE).write(0x00);
+            register(base, LINE_CONTROL).write(0x80); // DLAB on to set the divisor
+            register(base, DATA).write(divisor as u8);
+            register(base, INTERRUPT_ENABLE).write((divisor >> 8) as u8);
+            register(base, LINE_CONTROL).write(0x03); // 8 data bits, no parity, 1 stop bit
+            register(base, FIFO_CONTROL).write(0xC7); // Enable and clear FIFOs, 14 byte threshold
+            register(base, MODEM_CONTROL).write(0x0B); // DTR, RTS and OUT2, which gates the IRQ line
+            register(base, INTERRUPT_ENABLE).write(IER_RECEIVED);
+        }
+        self.baud = UART_CLOCK / divisor as u32;
+        self.initialized = true;
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let base = self.port.base();
+        let queued = INTERRUPTS_STARTED.load(Ordering::Relaxed);
+        for &byte in bytes {
+            if !queued {
+                self.flush_polling();
+                transmit_polling(base, byte);
+                continue;
+            }
+            if self.tx.is_full() {
+                self.flush_polling();
+            }
+            self.tx.push(byte);
+        }
+        if queued {
+            self.set_transmit_interrupt(true);
+        }
+    }
+
+    /// Sends everything still queued by polling the line status register.
+    fn flush_polling(&mut self) {
+        let base = self.port.base();
+        while let Some(byte) = self.tx.pop() {
+            transmit_polling(base, byte);
+        }
+    }
+
+    fn set_transmit_interrupt(&mut self, enabled: bool) {
+        let value = if enabled { IER_RECEIVED | IER_TRANSMIT_EMPTY } else { IER_RECEIVED };
+        unsafe { register(self.port.base(), INTERRUPT_ENABLE).write(value) };
+    }
+
+    fn service(&mut self) {
+        let base = self.port.base();
+        unsafe {
+            // Bit 0 of the interrupt identification register is clear while an interrupt is pending
+            while register(base, INTERRUPT_ID).read() & 0x01 == 0 {
+                while register(base, LINE_STATUS).read() & LSR_DATA_READY != 0 {
+                    let byte = register(base, DATA).read();
+                    if !self.rx.push(byte) {
+                        self.overruns += 1;
+                    }
+                }
+                if register(base, LINE_STATUS).read() & LSR_TRANSMIT_EMPTY != 0 {
+                    for _ in 0..FIFO_DEPTH {
+                        match self.tx.pop() {
+                            Some(byte) => register(base, DATA).write(byte),
+                            None => break,
+                        }
+                    }
+                    if self.tx.len == 0 {
+                        self.set_transmit_interrupt(false);
+                    }
+                }
+            }
+        }
+    }
+}
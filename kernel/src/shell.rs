@@ -0,0 +1,374 @@
+//! Debug shell on COM1. Input is collected by `poll`, which the CPU loop calls whenever it
+//! wakes up, and each complete line is run as a command. Type `help` for the list.
+
+use alloc::format;
+use core::fmt::{self, Write};
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use kernel::serial::{self, ComPort, Serial};
+use spin::Mutex;
+use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+use x86_64::registers::model_specific::Efer;
+use x86_64::registers::rflags;
+use crate::interrupts::{read_lapic, APICOffset};
+use crate::remote::{self, Session};
+use pong::Physics;
+use crate::{allocator, power, telemetry, with_game};
+
+const PROMPT: &str = "> ";
+const MAX_LINE: usize = 96;
+const PAGE_SIZE: u64 = 4096;
+/// Most bytes `peek` dumps at once.
+const MAX_PEEK: u64 = 1024;
+
+type Command = fn(&mut Args, &mut Serial) -> Result<(), ShellError>;
+
+const COMMANDS: [(&str, &str, Command); 14] = [
+    ("help", "list commands", help),
+    ("mem", "frame and heap usage", mem),
+    ("regs", "control registers and flags", regs),
+    ("apic", "dump the local APIC registers", apic),
+    ("irq", "interrupt counts", irq),
+    ("game", "game state | game set <speed|max|step|angle> <n> (px/s, degrees)", game),
+    ("seed", "seed [n]: show or set the game's random seed, to replay serves", seed),
+    ("peek", "peek <phys addr> [len]: hex dump physical memory", peek),
+    ("poke", "poke <phys addr> <byte>...: write bytes to physical memory", poke),
+    ("log", "log <level>: set the log level (off, error, warn, info, debug, trace)", log_level),
+    ("telemetry", "telemetry [on|off]: game events on COM2", telemetry),
+    ("remote", "switch to the JSON control protocol for scripts (see remote.rs)", remote),
+    ("reboot", "reset the machine", reboot),
+    ("shutdown", "power off through ACPI", shutdown),
+];
+
+enum ShellError {
+    Usage(&'static str),
+    Invalid(&'static str),
+    Output,
+}
+
+impl From<fmt::Error> for ShellError {
+    fn from(_: fmt::Error) -> Self {
+        ShellError::Output
+    }
+}
+
+/// What the shell knows about physical memory, handed over by `kernel_main`.
+pub struct MemoryInfo {
+    pub physical_offset: u64,
+    pub regions: &'static [MemoryRegion],
+    pub frames_allocated: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Interactive,
+    /// Lines go to `remote::execute`, without echo or prompt.
+    Remote,
+}
+
+struct Shell {
+    line: [u8; MAX_LINE],
+    len: usize,
+    memory: Option<MemoryInfo>,
+    mode: Mode,
+    after_cr: bool,
+}
+
+static SHELL: Mutex<Shell> = Mutex::new(Shell {
+    line: [0; MAX_LINE],
+    len: 0,
+    memory: None,
+    mode: Mode::Interactive,
+    after_cr: false,
+});
+
+pub fn init(memory: MemoryInfo) {
+    SHELL.lock().memory = Some(memory);
+    let _ = write!(kernel::serial(), "Debug shell on COM1, type `help` for commands\r\n{PROMPT}");
+}
+
+/// Handles the bytes received since the last call, echoing them and running complete lines.
+pub fn poll() {
+    let mut shell = SHELL.lock();
+    let mut out = kernel::serial();
+    while let Some(byte) = serial::read_byte(ComPort::Com1) {
+        let interactive = shell.mode == Mode::Interactive;
+        let after_cr = core::mem::replace(&mut shell.after_cr, byte == b'\r');
+        match byte {
+            // Treat \r\n as one line ending
+            b'\n' if after_cr => {}
+            b'\r' | b'\n' => {
+                let len = shell.len;
+                shell.len = 0;
+                let line = core::str::from_utf8(&shell.line[..len]).unwrap_or("");
+                if interactive {
+                    let _ = out.write_str("\r\n");
+                    if execute(line, shell.memory.as_ref(), &mut out) {
+                        shell.mode = Mode::Remote;
+                    } else {
+                        let _ = out.write_str(PROMPT);
+                    }
+                } else if let Session::Exit = remote::execute(line, &mut out) {
+                    shell.mode = Mode::Interactive;
+                    let _ = out.write_str(PROMPT);
+                }
+            }
+            // Backspace and delete
+            0x08 | 0x7f if shell.len > 0 => {
+                shell.len -= 1;
+                if interactive {
+                    let _ = out.write_str("\x08 \x08");
+                }
+            }
+            byte if (byte.is_ascii_graphic() || byte == b' ') && shell.len < MAX_LINE => {
+                let len = shell.len;
+                shell.line[len] = byte;
+                shell.len += 1;
+                if interactive {
+                    let _ = out.write_char(byte as char);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whitespace-separated words of a command line, after the command name.
+struct Args<'a> {
+    words: core::str::SplitAsciiWhitespace<'a>,
+    memory: Option<&'a MemoryInfo>,
+    /// Set by the `remote` command to switch modes once it returns.
+    enter_remote: bool,
+}
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        self.words.next()
+    }
+
+    fn number(&mut self, usage: &'static str) -> Result<u64, ShellError> {
+        let word = self.next().ok_or(ShellError::Usage(usage))?;
+        parse_number(word).ok_or(ShellError::Invalid("expected a number, decimal or 0x hex"))
+    }
+
+    fn memory(&self) -> Result<&'a MemoryInfo, ShellError> {
+        self.memory.ok_or(ShellError::Invalid("memory information not available yet"))
+    }
+}
+
+fn parse_number(word: &str) -> Option<u64> {
+    match word.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => word.parse().ok(),
+    }
+}
+
+/// Runs a command line. Returns true if the shell should switch to remote mode.
+fn execute(line: &str, memory: Option<&MemoryInfo>, out: &mut Serial) -> bool {
+    let mut words = line.split_ascii_whitespace();
+    let Some(name) = words.next() else {
+        return false;
+    };
+    let mut args = Args { words, memory, enter_remote: false };
+    let result = match COMMANDS.iter().find(|(command, _, _)| *command == name) {
+        Some((_, _, command)) => command(&mut args, out),
+        None => Err(ShellError::Invalid("unknown command, try `help`")),
+    };
+    let _ = match result {
+        Ok(()) | Err(ShellError::Output) => Ok(()),
+        Err(ShellError::Usage(usage)) => writeln!(out, "usage: {usage}\r"),
+        Err(ShellError::Invalid(message)) => writeln!(out, "error: {message}\r"),
+    };
+    args.enter_remote
+}
+
+fn help(_: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    for (name, description, _) in COMMANDS {
+        writeln!(out, "  {name:<10} {description}\r")?;
+    }
+    Ok(())
+}
+
+fn mem(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    let memory = args.memory()?;
+    let usable: u64 = memory.regions.iter()
+        .filter(|region| region.kind == MemoryRegionKind::Usable)
+        .map(|region| (region.end - region.start) / PAGE_SIZE)
+        .sum();
+    writeln!(out, "frames: {} of {} usable allocated ({} KiB free)\r",
+        memory.frames_allocated, usable, (usable - memory.frames_allocated as u64) * PAGE_SIZE / 1024)?;
+    let used = allocator::used();
+    writeln!(out, "heap:   {} of {} KiB used ({}%)\r",
+        used / 1024, allocator::HEAP_SIZE / 1024, used * 100 / allocator::HEAP_SIZE)?;
+    Ok(())
+}
+
+fn regs(_: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    let rsp: u64;
+    unsafe { core::arch::asm!("mov {}, rsp", out(reg) rsp) };
+    let (level4_frame, pcid) = Cr3::read_raw();
+    writeln!(out, "cr0    {:#018x}\r", Cr0::read_raw())?;
+    writeln!(out, "cr2    {:#018x}\r", Cr2::read_raw())?;
+    writeln!(out, "cr3    {:#018x} (pcid {})\r", level4_frame.start_address().as_u64(), pcid)?;
+    writeln!(out, "cr4    {:#018x}\r", Cr4::read_raw())?;
+    writeln!(out, "efer   {:#018x}\r", Efer::read_raw())?;
+    writeln!(out, "rflags {:#018x}\r", rflags::read_raw())?;
+    writeln!(out, "rsp    {:#018x}\r", rsp)?;
+    Ok(())
+}
+
+fn apic(_: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    for offset in APICOffset::READABLE {
+        let value = read_lapic(offset).ok_or(ShellError::Invalid("local APIC not mapped"))?;
+        writeln!(out, "{:#05x} {:<8} {:#010x}\r", offset as isize, format!("{offset:?}"), value)?;
+    }
+    Ok(())
+}
+
+fn irq(_: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    for (name, count) in kernel::IRQ_NAMES.iter().zip(kernel::irq_counts()) {
+        writeln!(out, "{name:<9} {count}\r")?;
+    }
+    writeln!(out, "com1 overruns {}\r", serial::with_port(ComPort::Com1, |uart| uart.overruns()))?;
+    Ok(())
+}
+
+fn game(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    const USAGE: &str = "game state | game set <speed|max|step|angle> <n>";
+    match args.next() {
+        Some("state") => {
+            let (mode, ball, velocity, paddles, score, sets, physics, rules) = with_game(|game| (
+                (game.game_mode, game.players),
+                (game.ball_x.round(), game.ball_y.round()),
+                (game.ball_dx.round(), game.ball_dy.round(), game.ball_speed()),
+                (game.left_paddle, game.right_paddle),
+                (game.left_score, game.right_score),
+                (game.left_sets, game.right_sets),
+                game.physics,
+                (game.rules.summary(), game.difficulty),
+            ));
+            writeln!(out, "mode     {:?}, players {:?}\r", mode.0, mode.1)?;
+            writeln!(out, "ball     ({}, {}) velocity ({}, {}) px/s, speed {}\r", ball.0, ball.1, velocity.0, velocity.1, velocity.2)?;
+            writeln!(out, "physics  serve {} step {} max {} px/s, max angle {} deg\r",
+                physics.serve_speed, physics.speed_step, physics.max_speed, physics.max_bounce_angle)?;
+            writeln!(out, "paddles  left {} right {}\r", paddles.0, paddles.1)?;
+            writeln!(out, "score    {} - {}, sets {} - {}\r", score.0, score.1, sets.0, sets.1)?;
+            writeln!(out, "rules    {}, CPU {}\r", rules.0, rules.1.name())?;
+        }
+        Some("set") => {
+            let setting = args.next().ok_or(ShellError::Usage(USAGE))?;
+            let value = args.number(USAGE)?;
+            let value = i32::try_from(value).map_err(|_| ShellError::Invalid("value out of range"))?;
+            match setting {
+                "speed" => with_game(|game| game.set_ball_speed(value)),
+                "max" if !(1..=Physics::SPEED_LIMIT).contains(&value) => {
+                    return Err(ShellError::Invalid("max must be from 1 up to the speed limit"));
+                }
+                "max" => with_game(|game| game.physics.max_speed = value),
+                "step" if !(0..=Physics::SPEED_LIMIT).contains(&value) => {
+                    return Err(ShellError::Invalid("step must be from 0 up to the speed limit"));
+                }
+                "step" => with_game(|game| game.physics.speed_step = value),
+                "angle" => with_game(|game| game.physics.max_bounce_angle = value.min(Physics::MAX_BOUNCE_ANGLE)),
+                _ => return Err(ShellError::Usage(USAGE)),
+            }
+        }
+        _ => return Err(ShellError::Usage(USAGE)),
+    }
+    Ok(())
+}
+
+fn seed(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    if let Some(word) = args.next() {
+        let seed = parse_number(word).ok_or(ShellError::Usage("seed [n]"))?;
+        with_game(|game| game.reseed(seed));
+    }
+    writeln!(out, "seed {:#x}\r", with_game(|game| game.seed()))?;
+    Ok(())
+}
+
+/// Virtual address of `len` bytes of physical memory at `address`, if the range is mapped.
+fn physical_range(memory: &MemoryInfo, address: u64, len: u64) -> Result<*mut u8, ShellError> {
+    let end = address.checked_add(len).ok_or(ShellError::Invalid("address out of range"))?;
+    let mapped = memory.regions.iter().any(|region| region.start <= address && end <= region.end);
+    if !mapped {
+        return Err(ShellError::Invalid("address range not in the physical memory map"));
+    }
+    Ok((memory.physical_offset + address) as *mut u8)
+}
+
+fn peek(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    const USAGE: &str = "peek <phys addr> [len]";
+    let address = args.number(USAGE)?;
+    let len = match args.next() {
+        Some(word) => parse_number(word).ok_or(ShellError::Usage(USAGE))?.min(MAX_PEEK),
+        None => 64,
+    };
+    let pointer = physical_range(args.memory()?, address, len)?;
+    for line_start in (0..len).step_by(16) {
+        write!(out, "{:#014x}:", address + line_start)?;
+        for i in line_start..(line_start + 16).min(len) {
+            let byte = unsafe { pointer.add(i as usize).read_volatile() };
+            write!(out, " {byte:02x}")?;
+        }
+        out.write_str("\r\n")?;
+    }
+    Ok(())
+}
+
+fn poke(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    const USAGE: &str = "poke <phys addr> <byte>...";
+    let address = args.number(USAGE)?;
+    let mut bytes = [0u8; 16];
+    let mut count = 0;
+    while let Some(word) = args.next() {
+        let value = parse_number(word).ok_or(ShellError::Usage(USAGE))?;
+        if count == bytes.len() {
+            return Err(ShellError::Invalid("at most 16 bytes at a time"));
+        }
+        bytes[count] = u8::try_from(value).map_err(|_| ShellError::Invalid("values must be bytes"))?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(ShellError::Usage(USAGE));
+    }
+    let pointer = physical_range(args.memory()?, address, count as u64)?;
+    for (i, &byte) in bytes[..count].iter().enumerate() {
+        unsafe { pointer.add(i).write_volatile(byte) };
+    }
+    writeln!(out, "wrote {count} byte(s) at {address:#x}\r")?;
+    Ok(())
+}
+
+fn log_level(args: &mut Args, _: &mut Serial) -> Result<(), ShellError> {
+    const USAGE: &str = "log <off|error|warn|info|debug|trace>";
+    let level = args.next().ok_or(ShellError::Usage(USAGE))?;
+    let level = level.parse().map_err(|_| ShellError::Usage(USAGE))?;
+    kernel::logger::set_level(level);
+    Ok(())
+}
+
+fn telemetry(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    match args.next() {
+        Some("on") => telemetry::set_enabled(true),
+        Some("off") => telemetry::set_enabled(false),
+        Some(_) => return Err(ShellError::Usage("telemetry [on|off]")),
+        None => {}
+    }
+    let state = if telemetry::is_enabled() { "on" } else { "off" };
+    writeln!(out, "telemetry {state}\r")?;
+    Ok(())
+}
+
+fn remote(args: &mut Args, out: &mut Serial) -> Result<(), ShellError> {
+    args.enter_remote = true;
+    write!(out, "{{\"ok\":true,\"remote\":true}}\r\n")?;
+    Ok(())
+}
+
+fn reboot(_: &mut Args, _: &mut Serial) -> Result<(), ShellError> {
+    power::reboot()
+}
+
+fn shutdown(_: &mut Args, _: &mut Serial) -> Result<(), ShellError> {
+    power::shutdown()
+}
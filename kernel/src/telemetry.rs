@@ -0,0 +1,58 @@
+//! Game events for balancing analysis, written to COM2 as one flat JSON object per line, e.g.
+//! `{"tick":812,"event":"paddle_hit","side":"left","offset":-12,"dx":75,"dy":-3}`.
+//!
+//! Telemetry switches itself on at boot when a UART answers on COM2 (the runner's
+//! `--telemetry` option attaches one) and can be toggled from the shell. The runner decodes
+//! the stream into CSV or JSONL.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use kernel::serial::{self, ComPort, Serial};
+use pong::Event;
+
+const PORT: ComPort = ComPort::Com2;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns telemetry on if something is listening on COM2.
+pub fn init() {
+    if serial::is_present(PORT) {
+        set_enabled(true);
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn emit(event: Event) {
+    if !is_enabled() {
+        return;
+    }
+    let mut out = Serial(PORT);
+    let _ = write!(out, "{{\"tick\":{}", kernel::ticks());
+    let _ = match event {
+        Event::Serve { side, dx, dy } => write!(out,
+            ",\"event\":\"serve\",\"side\":\"{}\",\"dx\":{dx},\"dy\":{dy}", side.name()),
+        Event::PaddleHit { side, offset, dx, dy } => write!(out,
+            ",\"event\":\"paddle_hit\",\"side\":\"{}\",\"offset\":{offset},\"dx\":{dx},\"dy\":{dy}", side.name()),
+        Event::WallBounce { x, dx, dy } => write!(out, ",\"event\":\"wall_bounce\",\"x\":{x},\"dx\":{dx},\"dy\":{dy}"),
+        Event::Point { side, left, right } => write!(out,
+            ",\"event\":\"point\",\"side\":\"{}\",\"left_score\":{left},\"right_score\":{right}", side.name()),
+        Event::SetOver { winner, left, right } => write!(out,
+            ",\"event\":\"set_over\",\"side\":\"{}\",\"left_score\":{left},\"right_score\":{right}", winner.name()),
+        Event::GameOver { winner, left, right } => write!(out,
+            ",\"event\":\"game_over\",\"side\":\"{}\",\"left_score\":{left},\"right_score\":{right}", winner.name()),
+        Event::Frame { cycles } => write!(out, ",\"event\":\"frame\",\"cycles\":{cycles}"),
+    };
+    let _ = out.write_str("}\n");
+}
+
+/// Reads the time stamp counter, for measuring frame times.
+pub fn cycles() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
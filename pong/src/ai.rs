@@ -0,0 +1,141 @@
+//! The CPU player. It predicts where the ball will cross its paddle, bouncing the path off the
+//! walls, then moves there at a limited speed. Lower difficulties react later, move slower
+//! and misjudge the intercept; higher ones also aim their returns away from the opponent.
+
+use crate::event::Side;
+use crate::fixed::Fixed;
+use crate::rng::Pcg32;
+
+/// Furthest ahead the prediction looks, in seconds, which keeps the unfolded path in range
+/// for a slow, steep ball.
+const MAX_LOOKAHEAD: Fixed = Fixed::from_int(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Impossible,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Impossible];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+            Difficulty::Impossible => "IMPOSSIBLE",
+        }
+    }
+
+    pub fn profile(self) -> AiProfile {
+        match self {
+            Difficulty::Easy => AiProfile { reaction_ms: 300, speed: 300, aim_error: 60, aim: 0 },
+            Difficulty::Normal => AiProfile { reaction_ms: 160, speed: 450, aim_error: 25, aim: 0 },
+            Difficulty::Hard => AiProfile { reaction_ms: 80, speed: 700, aim_error: 10, aim: 40 },
+            Difficulty::Impossible => AiProfile { reaction_ms: 0, speed: 3000, aim_error: 0, aim: 70 },
+        }
+    }
+}
+
+/// How well the CPU plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AiProfile {
+    /// Delay before the paddle starts moving toward a ball that has turned toward it.
+    pub reaction_ms: u32,
+    /// Top paddle speed, in pixels per second.
+    pub speed: i32,
+    /// Largest mistake in the predicted intercept, in pixels either way.
+    pub aim_error: i32,
+    /// How far off center, in percent of half the paddle, it tries to meet the ball so the
+    /// return leaves at an angle away from the opponent. 0 meets it dead center.
+    pub aim: i32,
+}
+
+/// What the AI sees of the game in a frame. Positions are top-left corners in pixels and
+/// velocities are in pixels per second.
+#[derive(Debug, Clone, Copy)]
+pub struct AiView {
+    pub ball_x: Fixed,
+    pub ball_y: Fixed,
+    pub ball_dx: Fixed,
+    pub ball_dy: Fixed,
+    pub ball_size: i32,
+    /// The ball's `x` when it touches this paddle's face.
+    pub contact_x: Fixed,
+    pub paddle_y: isize,
+    pub opponent_y: isize,
+    pub paddle_height: i32,
+    pub field_height: i32,
+    pub frame_rate: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ai {
+    pub profile: AiProfile,
+    side: Side,
+    rng: Pcg32,
+    /// Whether the ball was coming this way last frame, to notice when it turns.
+    approaching: bool,
+    /// Frames left before reacting to the current approach.
+    reaction_left: u32,
+    /// Mistake and aim chosen for the current approach, in pixels.
+    error: i32,
+    aim_offset: i32,
+}
+
+impl Ai {
+    pub fn new(profile: AiProfile, side: Side, seed: u64) -> Self {
+        Ai { profile, side, rng: Pcg32::new(seed), approaching: false, reaction_left: 0, error: 0, aim_offset: 0 }
+    }
+
+    /// Where the paddle should be after this frame.
+    pub fn step(&mut self, view: &AiView) -> isize {
+        let approaching = match self.side {
+            Side::Left => view.ball_dx < Fixed::ZERO,
+            Side::Right => view.ball_dx > Fixed::ZERO,
+        };
+        if approaching && !self.approaching {
+            self.reaction_left = self.profile.reaction_ms * view.frame_rate / 1000;
+            self.error = self.rng.range(-self.profile.aim_error, self.profile.aim_error);
+            // Send the ball toward the side of the field the opponent is farther from
+            let opponent_center = view.opponent_y as i32 + view.paddle_height / 2;
+            let away = if opponent_center < view.field_height / 2 { 1 } else { -1 };
+            self.aim_offset = away * self.profile.aim * view.paddle_height / 200;
+        }
+        self.approaching = approaching;
+
+        let target = if !approaching {
+            // Wait in the middle for the next return
+            (view.field_height - view.paddle_height) / 2
+        } else if self.reaction_left > 0 {
+            self.reaction_left -= 1;
+            return view.paddle_y;
+        } else {
+            let intercept = predict_intercept(view).round();
+            intercept + view.ball_size / 2 + self.error - self.aim_offset - view.paddle_height / 2
+        };
+        let target = target.clamp(0, view.field_height - view.paddle_height) as isize;
+        let max_step = (self.profile.speed / view.frame_rate as i32).max(1) as isize;
+        view.paddle_y + (target - view.paddle_y).clamp(-max_step, max_step)
+    }
+}
+
+/// The ball's `y` when it reaches `contact_x`, following its path as it bounces off the top
+/// and bottom walls.
+pub fn predict_intercept(view: &AiView) -> Fixed {
+    if view.ball_dx == Fixed::ZERO {
+        return view.ball_y;
+    }
+    let seconds = ((view.contact_x - view.ball_x) / view.ball_dx).clamp(Fixed::ZERO, MAX_LOOKAHEAD);
+    let y = view.ball_y + view.ball_dy * seconds;
+    // Unfold the bounces: the path repeats every two field heights, mirrored in the second
+    let span = Fixed::from_int(view.field_height - view.ball_size);
+    if span <= Fixed::ZERO {
+        return Fixed::ZERO;
+    }
+    let folded = y.rem_euclid(span * 2);
+    if folded > span { span * 2 - folded } else { folded }
+}
@@ -0,0 +1,127 @@
+//! Swept collision detection between axis-aligned boxes, independent of rendering.
+//!
+//! Instead of moving the ball and then checking for overlap, which lets a fast ball tunnel
+//! through a paddle, `sweep` finds the time of impact along the whole move and which face
+//! of the obstacle was hit.
+
+use crate::fixed::Fixed;
+
+/// Axis-aligned box; `x`/`y` is the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub x: Fixed,
+    pub y: Fixed,
+    pub width: Fixed,
+    pub height: Fixed,
+}
+
+impl Aabb {
+    pub fn new(x: Fixed, y: Fixed, width: Fixed, height: Fixed) -> Self {
+        Aabb { x, y, width, height }
+    }
+
+    pub fn right(&self) -> Fixed {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> Fixed {
+        self.y + self.height
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.x < other.right() && other.x < self.right() && self.y < other.bottom() && other.y < self.bottom()
+    }
+}
+
+/// Face of the obstacle that was hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// Fraction of the move completed at the moment of contact, from 0 to 1.
+    pub time: Fixed,
+    pub face: Face,
+    /// Where the moving box is at contact, touching `face` without overlapping.
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+/// Sweeps `moving` by (`dx`, `dy`) against a stationary `obstacle`. Returns the first contact
+/// during the move, if any. A box that already overlaps the obstacle (say a paddle moved onto
+/// the ball) hits at time 0 on the face it penetrates least, so it can be pushed back out.
+pub fn sweep(moving: Aabb, dx: Fixed, dy: Fixed, obstacle: Aabb) -> Option<Hit> {
+    if moving.overlaps(&obstacle) {
+        return Some(push_out(moving, obstacle));
+    }
+
+    let (x_entry, x_exit) = axis_times(moving.x, moving.right(), dx, obstacle.x, obstacle.right())?;
+    let (y_entry, y_exit) = axis_times(moving.y, moving.bottom(), dy, obstacle.y, obstacle.bottom())?;
+    let entry = x_entry.max(y_entry);
+    let exit = x_exit.min(y_exit);
+    // Boxes that only touch at the end of the move (entry == 1) haven't collided yet
+    if entry > exit || entry < Fixed::ZERO || entry >= Fixed::ONE {
+        return None;
+    }
+
+    let (mut x, mut y) = (moving.x + dx * entry, moving.y + dy * entry);
+    let face = if x_entry > y_entry {
+        if dx > Fixed::ZERO {
+            x = obstacle.x - moving.width;
+            Face::Left
+        } else {
+            x = obstacle.right();
+            Face::Right
+        }
+    } else if dy > Fixed::ZERO {
+        y = obstacle.y - moving.height;
+        Face::Top
+    } else {
+        y = obstacle.bottom();
+        Face::Bottom
+    };
+    // Touching along the other axis (e.g. sliding past a corner) isn't a collision
+    let contact = Aabb::new(x, y, moving.width, moving.height);
+    let touching = match face {
+        Face::Left | Face::Right => contact.y < obstacle.bottom() && obstacle.y < contact.bottom(),
+        Face::Top | Face::Bottom => contact.x < obstacle.right() && obstacle.x < contact.right(),
+    };
+    touching.then_some(Hit { time: entry, face, x, y })
+}
+
+/// Entry and exit times on one axis, as fractions of the move. Without motion on the axis
+/// the boxes overlap on it either always or never, the latter meaning no collision at all.
+fn axis_times(start: Fixed, end: Fixed, delta: Fixed, obstacle_start: Fixed, obstacle_end: Fixed) -> Option<(Fixed, Fixed)> {
+    const NEVER: Fixed = Fixed::from_int(i16::MAX as i32);
+    if delta == Fixed::ZERO {
+        return if start < obstacle_end && obstacle_start < end { Some((-NEVER, NEVER)) } else { None };
+    }
+    let (entry, exit) = if delta > Fixed::ZERO {
+        (obstacle_start - end, obstacle_end - start)
+    } else {
+        (obstacle_end - start, obstacle_start - end)
+    };
+    Some((entry / delta, exit / delta))
+}
+
+fn push_out(moving: Aabb, obstacle: Aabb) -> Hit {
+    let candidates = [
+        (moving.right() - obstacle.x, Face::Left),
+        (obstacle.right() - moving.x, Face::Right),
+        (moving.bottom() - obstacle.y, Face::Top),
+        (obstacle.bottom() - moving.y, Face::Bottom),
+    ];
+    let (_, face) = candidates.into_iter().min_by_key(|(depth, _)| *depth).unwrap();
+    let (x, y) = match face {
+        Face::Left => (obstacle.x - moving.width, moving.y),
+        Face::Right => (obstacle.right(), moving.y),
+        Face::Top => (moving.x, obstacle.y - moving.height),
+        Face::Bottom => (moving.x, obstacle.bottom()),
+    };
+    Hit { time: Fixed::ZERO, face, x, y }
+}
@@ -0,0 +1,41 @@
+//! Things that happen during a match, reported to the game's event handler so the host can
+//! record them (the kernel streams them as telemetry).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// `side` served the ball from the middle of the field.
+    Serve { side: Side, dx: i32, dy: i32 },
+    /// `offset` is the distance of the ball's center from the paddle's center, negative above.
+    PaddleHit { side: Side, offset: i32, dx: i32, dy: i32 },
+    WallBounce { x: i32, dx: i32, dy: i32 },
+    /// `side` scored; the scores are after the point.
+    Point { side: Side, left: u8, right: u8 },
+    /// `winner` took a set that ended at `left`-`right`.
+    SetOver { winner: Side, left: u8, right: u8 },
+    /// The scores are those of the last set.
+    GameOver { winner: Side, left: u8, right: u8 },
+    /// Time spent updating and drawing one frame.
+    Frame { cycles: u64 },
+}
@@ -0,0 +1,146 @@
+//! 16.16 fixed-point numbers for the game physics, so positions and velocities keep their
+//! fractional part without using floating point in the kernel.
+
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+const FRAC_BITS: u32 = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+    /// Pi / 180, for converting degrees to radians.
+    const RADIANS_PER_DEGREE: Fixed = Fixed(1144);
+
+    pub const fn from_int(value: i32) -> Self {
+        Fixed(value << FRAC_BITS)
+    }
+
+    /// `numerator / denominator` without going through an integer division first.
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        Fixed((((numerator as i64) << FRAC_BITS) / denominator as i64) as i32)
+    }
+
+    pub fn from_degrees(degrees: i32) -> Self {
+        Fixed::RADIANS_PER_DEGREE * degrees
+    }
+
+    /// Nearest integer, halves rounding up.
+    pub const fn round(self) -> i32 {
+        (self.0 + (1 << (FRAC_BITS - 1))) >> FRAC_BITS
+    }
+
+    pub const fn abs(self) -> Self {
+        Fixed(self.0.abs())
+    }
+
+    /// Remainder of dividing by `modulus`, never negative.
+    pub fn rem_euclid(self, modulus: Fixed) -> Self {
+        Fixed(self.0.rem_euclid(modulus.0))
+    }
+
+    pub fn clamp(self, min: Fixed, max: Fixed) -> Self {
+        Fixed(self.0.clamp(min.0, max.0))
+    }
+
+    /// Sine of an angle in radians. Accurate to about 1e-3 for angles up to +-pi/2, which
+    /// is all the physics needs.
+    pub fn sin(self) -> Self {
+        // Taylor series x - x^3/3! + x^5/5! - x^7/7!
+        let x = self;
+        let x2 = x * x;
+        let mut term = x;
+        let mut sum = x;
+        for divisor in [6, 20, 42] {
+            term = -(term * x2) / divisor;
+            sum += term;
+        }
+        sum
+    }
+
+    /// Cosine of an angle in radians, with the same range as `sin`.
+    pub fn cos(self) -> Self {
+        // Taylor series 1 - x^2/2! + x^4/4! - x^6/6! + x^8/8!
+        let x2 = self * self;
+        let mut term = Fixed::ONE;
+        let mut sum = Fixed::ONE;
+        for divisor in [2, 12, 30, 56] {
+            term = -(term * x2) / divisor;
+            sum += term;
+        }
+        sum
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * other.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: i32) -> Fixed {
+        Fixed(self.0 * other)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    /// Saturates instead of wrapping, since dividing by a tiny velocity is expected when
+    /// working out collision times.
+    fn div(self, other: Fixed) -> Fixed {
+        let quotient = ((self.0 as i64) << FRAC_BITS) / other.0 as i64;
+        Fixed(quotient.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+impl Div<i32> for Fixed {
+    type Output = Fixed;
+
+    fn div(self, other: i32) -> Fixed {
+        Fixed(self.0 / other)
+    }
+}
@@ -0,0 +1,1045 @@
+//! The Pong simulation: menu, match state, ball physics and the CPU player, plus drawing
+//! through a `Renderer`. The kernel owns one `PongGame`, feeds it key presses and calls
+//! `update` and `draw` once per timer tick.
+//!
+//! A match moves through the `GameMode` states Serve, Playing and PointScored until a player
+//! wins, then GameOver shows the results. Transitions go through `set_mode`, which runs the
+//! exit hook of the old state and the enter hook of the new one.
+
+use core::fmt::Write;
+use crate::ai::{Ai, AiView, Difficulty};
+use crate::block_font;
+use crate::collision::{self, Aabb, Face};
+use crate::event::{Event, Side};
+use crate::fixed::Fixed;
+use crate::render::{Rect, Renderer, TextBuffer, TextStyle};
+use crate::rng::Pcg32;
+use crate::rules::{MatchRules, ServeRule, MAX_TARGET_SCORE, SET_COUNTS, TIME_LIMITS};
+use crate::stats::MatchStats;
+use crate::theme::{Theme, THEMES};
+
+const MENU_ITEMS: [&str; 4] = ["1 PLAYER", "2 PLAYERS", "SETTINGS", "QUIT"];
+const MENU_SETTINGS: usize = 2;
+const MENU_QUIT: usize = 3;
+
+const PAUSE_ITEMS: [&str; 5] = ["RESUME", "RESTART", "SETTINGS", "QUIT TO MENU", "QUIT"];
+const PAUSE_TO_MENU: usize = 3;
+/// Opacity of the shade over the playfield while paused.
+const PAUSE_DIM: u8 = 160;
+
+/// Rows of the settings screen: one per `MatchRules` field, the CPU difficulty, then BACK.
+const SETTING_ROWS: usize = 7;
+const SETTING_BACK: usize = 6;
+
+/// Seconds the menu waits for a key before the CPU starts playing itself.
+const ATTRACT_DELAY: u32 = 20;
+/// How well both sides play in attract mode. Good enough for long rallies, not so good that
+/// nobody ever scores.
+const ATTRACT_DIFFICULTY: Difficulty = Difficulty::Hard;
+
+/// Size of one block-font pixel in the in-game score, and its distance from the center line.
+const SCORE_SCALE: usize = 8;
+const SCORE_GAP: usize = 40;
+/// Size of one block-font pixel in the serve countdown and the point flash.
+const BANNER_SCALE: usize = 8;
+
+/// Tunables of the ball physics. Speeds are in pixels per second, angles in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Physics {
+    pub serve_speed: i32,
+    /// Added to the ball speed on every paddle hit of a rally.
+    pub speed_step: i32,
+    pub max_speed: i32,
+    /// Largest serve angle either side of horizontal.
+    pub max_serve_angle: i32,
+    /// Angle the ball leaves at when it hits the very end of a paddle.
+    pub max_bounce_angle: i32,
+}
+
+impl Physics {
+    pub const DEFAULT: Physics = Physics {
+        serve_speed: 420,
+        speed_step: 30,
+        max_speed: 1500,
+        max_serve_angle: 30,
+        max_bounce_angle: 60,
+    };
+    /// Above this the ball would barely move sideways.
+    pub const MAX_BOUNCE_ANGLE: i32 = 75;
+    /// Highest `max_speed` allowed. `Fixed` only reaches 32767, and the CPU multiplies speeds
+    /// by up to 10 seconds of lookahead.
+    pub const SPEED_LIMIT: i32 = 3000;
+}
+
+/// Seed of a new game until the host calls `reseed`.
+const DEFAULT_SEED: u64 = 42;
+
+/// `Pcg32` stream of the attract mode generator.
+const DEMO_STREAM: u64 = 1;
+
+/// Paddle bounces handled in one frame before the rest of the move is dropped.
+const MAX_HITS_PER_FRAME: usize = 3;
+
+/// Seconds counted down before each serve.
+const SERVE_COUNTDOWN: u32 = 3;
+/// How long play stops after a point, flashing "POINT!".
+const POINT_DELAY_MS: u32 = 1000;
+/// Times per second the point flash blinks on.
+const POINT_FLASH_HZ: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    Menu,
+    Settings,
+    /// The ball waits in the middle of the field while a countdown runs.
+    Serve,
+    /// The ball is in play.
+    Playing,
+    /// A short stop after the ball left the field, before the next serve or the end of the set.
+    PointScored,
+    /// A match stopped with Esc or P, showing the pause menu.
+    Paused,
+    /// The results screen.
+    GameOver,
+}
+
+/// Who is playing the current match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Players {
+    /// Player 1 against the CPU.
+    One,
+    Two,
+    /// The CPU playing both sides behind the menu while nobody is at the keyboard.
+    Demo,
+}
+
+/// Something the game needs its host to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// QUIT was picked in the menu.
+    Quit,
+}
+
+pub struct PongGame {
+    /// Top-left corner of the ball, in pixels.
+    pub ball_x: Fixed,
+    pub ball_y: Fixed,
+    /// Ball velocity, in pixels per second.
+    pub ball_dx: Fixed,
+    pub ball_dy: Fixed,
+    /// Length of the velocity, which stays constant between paddle hits.
+    pub ball_speed: Fixed,
+    pub left_paddle: isize,
+    pub right_paddle: isize,
+    /// Points in the current set.
+    pub left_score: u8,
+    pub right_score: u8,
+    pub left_sets: u8,
+    pub right_sets: u8,
+    /// Who serves next, or is serving during `GameMode::Serve`.
+    pub server: Side,
+    /// Frames played in the current set, for timed sets.
+    pub set_frames: u32,
+    pub rules: MatchRules,
+    /// How well the CPU plays in one-player matches.
+    pub difficulty: Difficulty,
+    left_cpu: Ai,
+    right_cpu: Ai,
+    /// Frames the menu has gone without a key press.
+    idle_frames: u32,
+    pub width: usize,
+    pub height: usize,
+    pub paddle_width: usize,
+    pub paddle_height: usize,
+    pub ball_size: usize,
+    pub game_mode: GameMode,
+    pub players: Players,
+    /// Frames spent in the current `game_mode`.
+    mode_frames: u32,
+    pub stats: MatchStats,
+    pub selected_menu_item: usize,
+    pub selected_setting: usize,
+    pub selected_pause_item: usize,
+    /// The match mode to go back to while paused, including in settings opened from the pause
+    /// menu.
+    paused: Option<GameMode>,
+    pub physics: Physics,
+    pub winner: Option<&'static str>,
+    pub theme: usize,
+    /// Set by the remote protocol to stop the timer from advancing the simulation.
+    pub frozen: bool,
+    /// Simulation steps per second, i.e. how often the host calls `update`.
+    pub frame_rate: u32,
+    /// Seed `rng` started from, for replaying a match.
+    seed: u64,
+    /// Serves and CPU seeds of real matches.
+    rng: Pcg32,
+    /// The same for attract mode, so idling in the menu doesn't change how a seed replays.
+    demo_rng: Pcg32,
+    /// Called with every game event, e.g. to record telemetry.
+    pub on_event: fn(Event),
+    last_frame: Option<DrawnFrame>,
+}
+
+/// What the previous `PongGame::draw` put on screen, used to work out which areas changed.
+#[derive(Clone, PartialEq)]
+struct DrawnFrame {
+    game_mode: GameMode,
+    players: Players,
+    selected_menu_item: usize,
+    selected_setting: usize,
+    selected_pause_item: usize,
+    rules: MatchRules,
+    difficulty: Difficulty,
+    theme: usize,
+    left_paddle: Rect,
+    right_paddle: Rect,
+    ball: Rect,
+    hud: Hud,
+    banner: Option<TextBuffer>,
+}
+
+/// Everything shown in the HUD band.
+#[derive(Clone, PartialEq)]
+struct Hud {
+    score: (u8, u8),
+    sets: (u8, u8),
+    speed: i32,
+    seconds_left: Option<u32>,
+}
+
+impl PongGame {
+    pub fn new(width: usize, height: usize, frame_rate: u32) -> Self {
+        PongGame {
+            ball_x: Fixed::from_int((width / 2) as i32),
+            ball_y: Fixed::from_int((height / 2) as i32),
+            ball_dx: Fixed::ZERO,
+            ball_dy: Fixed::ZERO,
+            ball_speed: Fixed::ZERO,
+            left_paddle: (height / 2) as isize,
+            right_paddle: (height / 2) as isize,
+            left_score: 0,
+            right_score: 0,
+            left_sets: 0,
+            right_sets: 0,
+            server: Side::Left,
+            set_frames: 0,
+            rules: MatchRules::DEFAULT,
+            difficulty: Difficulty::Normal,
+            left_cpu: Ai::new(ATTRACT_DIFFICULTY.profile(), Side::Left, DEFAULT_SEED),
+            right_cpu: Ai::new(Difficulty::Normal.profile(), Side::Right, DEFAULT_SEED),
+            idle_frames: 0,
+            width,
+            height,
+            paddle_width: 15,
+            paddle_height: 80,
+            ball_size: 15,
+            game_mode: GameMode::Menu,
+            players: Players::One,
+            mode_frames: 0,
+            stats: MatchStats::default(),
+            selected_menu_item: 0,
+            selected_setting: 0,
+            selected_pause_item: 0,
+            paused: None,
+            physics: Physics::DEFAULT,
+            winner: None,
+            theme: 0,
+            frozen: false,
+            frame_rate,
+            seed: DEFAULT_SEED,
+            rng: Pcg32::new(DEFAULT_SEED),
+            demo_rng: Pcg32::with_stream(DEFAULT_SEED, DEMO_STREAM),
+            on_event: |_| {},
+            last_frame: None,
+        }
+    }
+
+    /// Advances the game by one frame.
+    pub fn update(&mut self) {
+        match self.game_mode {
+            GameMode::Menu => {
+                self.idle_frames += 1;
+                if self.idle_frames >= ATTRACT_DELAY * self.frame_rate {
+                    self.start_match(Players::Demo);
+                }
+            }
+            GameMode::Serve | GameMode::Playing | GameMode::PointScored => self.update_match(),
+            _ => {}
+        }
+    }
+
+    fn update_match(&mut self) {
+        self.mode_frames += 1;
+        self.stats.frames += 1;
+
+        // Running out of time, or a score changed from the shell, ends the set straight away
+        if self.game_mode != GameMode::PointScored
+            && let Some(winner) = self.rules.set_winner(self.left_score, self.right_score, self.time_up())
+        {
+            self.end_set(winner);
+            return;
+        }
+
+        match self.game_mode {
+            GameMode::Serve if self.mode_frames >= SERVE_COUNTDOWN * self.frame_rate => self.set_mode(GameMode::Playing),
+            GameMode::Playing => self.update_rally(),
+            GameMode::PointScored if self.mode_frames >= self.frames(POINT_DELAY_MS) => {
+                match self.rules.set_winner(self.left_score, self.right_score, self.time_up()) {
+                    Some(winner) => self.end_set(winner),
+                    None => self.set_mode(GameMode::Serve),
+                }
+            }
+            _ => {}
+        }
+
+        // CPU players
+        if self.shows_playfield() {
+            for &side in self.cpu_sides() {
+                let view = self.ai_view(side);
+                match side {
+                    Side::Left => self.left_paddle = self.left_cpu.step(&view),
+                    Side::Right => self.right_paddle = self.right_cpu.step(&view),
+                }
+            }
+        }
+    }
+
+    /// Moves the ball, and scores once it has left the field.
+    fn update_rally(&mut self) {
+        self.set_frames += 1;
+
+        // Move the ball, stopping at any paddle in its path and carrying on with the rest of
+        // the move from there, so a fast ball can't pass through a paddle between frames
+        let mut remaining = Fixed::ONE;
+        for _ in 0..MAX_HITS_PER_FRAME {
+            let dx = self.ball_dx * remaining / self.frame_rate as i32;
+            let dy = self.ball_dy * remaining / self.frame_rate as i32;
+            let ball = self.ball_box();
+            let hit = [Side::Left, Side::Right]
+                .into_iter()
+                .filter_map(|side| collision::sweep(ball, dx, dy, self.paddle_box(side)).map(|hit| (side, hit)))
+                .min_by_key(|(_, hit)| hit.time);
+            let Some((side, hit)) = hit else {
+                self.ball_x += dx;
+                self.ball_y += dy;
+                break;
+            };
+            self.ball_x = hit.x;
+            self.ball_y = hit.y;
+            remaining = remaining * (Fixed::ONE - hit.time);
+            self.bounce_off_paddle(side, hit.face);
+        }
+
+        // Wall collisions
+        let bottom = Fixed::from_int((self.height - self.ball_size) as i32);
+        if self.ball_y <= Fixed::ZERO {
+            self.ball_y = Fixed::ZERO;
+            self.ball_dy = self.ball_dy.abs();
+            self.emit_wall_bounce();
+        } else if self.ball_y >= bottom {
+            self.ball_y = bottom;
+            self.ball_dy = -self.ball_dy.abs();
+            self.emit_wall_bounce();
+        }
+
+        // A point is scored once the ball has left the field completely
+        if self.ball_x + Fixed::from_int(self.ball_size as i32) <= Fixed::ZERO {
+            self.right_score += 1;
+            self.score_point(Side::Right);
+        } else if self.ball_x >= Fixed::from_int(self.width as i32) {
+            self.left_score += 1;
+            self.score_point(Side::Left);
+        }
+    }
+
+    /// Follows up a point won by `scorer`, whose score has been counted.
+    fn score_point(&mut self, scorer: Side) {
+        self.emit(Event::Point { side: scorer, left: self.left_score, right: self.right_score });
+        self.server = self.rules.serve.next_server(self.server, scorer);
+        self.set_mode(GameMode::PointScored);
+    }
+
+    /// Moves to `mode`, running the exit hook of the current state and the enter hook of the
+    /// new one. Pausing and resuming assign `game_mode` directly, as the match stays where it
+    /// was.
+    fn set_mode(&mut self, mode: GameMode) {
+        // Exit hooks
+        if self.game_mode == GameMode::Playing {
+            self.stats.end_rally();
+        }
+
+        self.game_mode = mode;
+        self.mode_frames = 0;
+
+        // Enter hooks
+        match mode {
+            GameMode::Menu => self.idle_frames = 0,
+            GameMode::Serve => self.center_ball(),
+            GameMode::Playing => self.serve(),
+            _ => {}
+        }
+    }
+
+    /// Frames lasting `ms` milliseconds.
+    fn frames(&self, ms: u32) -> u32 {
+        ms * self.frame_rate / 1000
+    }
+
+    /// Credits `winner` with a set and either starts the next one or ends the match.
+    fn end_set(&mut self, winner: Side) {
+        if self.players == Players::Demo {
+            // Nobody is watching the result; just play another one
+            self.start_match(Players::Demo);
+            return;
+        }
+        self.emit(Event::SetOver { winner, left: self.left_score, right: self.right_score });
+        let sets = match winner {
+            Side::Left => &mut self.left_sets,
+            Side::Right => &mut self.right_sets,
+        };
+        *sets += 1;
+        if *sets < self.rules.sets_to_win() {
+            self.left_score = 0;
+            self.right_score = 0;
+            self.set_frames = 0;
+            self.set_mode(GameMode::Serve);
+            return;
+        }
+
+        self.winner = Some(match (winner, self.players) {
+            (Side::Left, _) => "PLAYER 1 WINS!",
+            (Side::Right, Players::One) => "CPU WINS!",
+            (Side::Right, _) => "PLAYER 2 WINS!",
+        });
+        self.emit(Event::GameOver { winner, left: self.left_score, right: self.right_score });
+        self.set_mode(GameMode::GameOver);
+    }
+
+    /// Whether a timed set has run out of time; play goes on in sudden death if it's tied.
+    pub fn time_up(&self) -> bool {
+        self.seconds_left() == Some(0)
+    }
+
+    /// Time left in a timed set, rounded up to whole seconds.
+    pub fn seconds_left(&self) -> Option<u32> {
+        let limit = self.rules.time_limit? as u32 * self.frame_rate;
+        Some(limit.saturating_sub(self.set_frames).div_ceil(self.frame_rate))
+    }
+
+    /// The paddles the CPU moves.
+    fn cpu_sides(&self) -> &'static [Side] {
+        match self.players {
+            Players::One => &[Side::Right],
+            Players::Two => &[],
+            Players::Demo => &[Side::Left, Side::Right],
+        }
+    }
+
+    /// The game as the CPU playing `side` sees it.
+    fn ai_view(&self, side: Side) -> AiView {
+        let (contact_x, paddle_y, opponent_y) = match side {
+            Side::Left => (self.paddle_width, self.left_paddle, self.right_paddle),
+            Side::Right => (self.width - self.paddle_width - self.ball_size, self.right_paddle, self.left_paddle),
+        };
+        AiView {
+            ball_x: self.ball_x,
+            ball_y: self.ball_y,
+            ball_dx: self.ball_dx,
+            ball_dy: self.ball_dy,
+            ball_size: self.ball_size as i32,
+            contact_x: Fixed::from_int(contact_x as i32),
+            paddle_y,
+            opponent_y,
+            paddle_height: self.paddle_height as i32,
+            field_height: self.height as i32,
+            frame_rate: self.frame_rate,
+        }
+    }
+
+    fn ball_box(&self) -> Aabb {
+        let size = Fixed::from_int(self.ball_size as i32);
+        Aabb::new(self.ball_x, self.ball_y, size, size)
+    }
+
+    fn paddle_box(&self, side: Side) -> Aabb {
+        let (x, y) = match side {
+            Side::Left => (0, self.left_paddle),
+            Side::Right => (self.width - self.paddle_width, self.right_paddle),
+        };
+        Aabb::new(
+            Fixed::from_int(x as i32),
+            Fixed::from_int(y as i32),
+            Fixed::from_int(self.paddle_width as i32),
+            Fixed::from_int(self.paddle_height as i32),
+        )
+    }
+
+    /// Responds to the ball touching `face` of a paddle. Only the face toward the field
+    /// deflects it by angle; the top and bottom edges just turn it away vertically, so it
+    /// usually goes on out of play.
+    fn bounce_off_paddle(&mut self, side: Side, face: Face) {
+        match (side, face) {
+            (Side::Left, Face::Right) => self.deflect(side, self.left_paddle),
+            (Side::Right, Face::Left) => self.deflect(side, self.right_paddle),
+            (_, Face::Left | Face::Right) => self.ball_dx = -self.ball_dx,
+            (_, Face::Top) => self.ball_dy = -self.ball_dy.abs(),
+            (_, Face::Bottom) => self.ball_dy = self.ball_dy.abs(),
+        }
+    }
+
+    /// Sends the ball back off a paddle. The angle depends on where it hit: straight back at
+    /// the center, up to `max_bounce_angle` at the ends. Every hit speeds the ball up.
+    fn deflect(&mut self, side: Side, paddle_y: isize) {
+        let ball_center = self.ball_y + Fixed::from_ratio(self.ball_size as i32, 2);
+        let paddle_center = Fixed::from_int(paddle_y as i32) + Fixed::from_ratio(self.paddle_height as i32, 2);
+        // Distance from the center at which the ball still touches the paddle
+        let reach = Fixed::from_ratio((self.paddle_height + self.ball_size) as i32, 2);
+        let offset = ball_center - paddle_center;
+        let position = (offset / reach).clamp(-Fixed::ONE, Fixed::ONE);
+
+        self.ball_speed = (self.ball_speed + Fixed::from_int(self.physics.speed_step))
+            .clamp(Fixed::ZERO, Fixed::from_int(self.physics.max_speed));
+        let direction = match side {
+            Side::Left => 1,
+            Side::Right => -1,
+        };
+        self.launch(position * Fixed::from_degrees(self.physics.max_bounce_angle), direction);
+        self.stats.hit();
+        self.emit(Event::PaddleHit {
+            side,
+            offset: offset.round(),
+            dx: self.ball_dx.round(),
+            dy: self.ball_dy.round(),
+        });
+    }
+
+    /// Points the ball at `angle` radians from horizontal (positive is down), moving right
+    /// for `direction` 1 and left for -1, at the current speed.
+    fn launch(&mut self, angle: Fixed, direction: i32) {
+        self.ball_dx = self.ball_speed * angle.cos() * direction;
+        self.ball_dy = self.ball_speed * angle.sin();
+    }
+
+    /// Changes the ball speed without changing its direction.
+    pub fn set_ball_speed(&mut self, speed: i32) {
+        let speed = Fixed::from_int(speed.clamp(1, self.physics.max_speed));
+        if self.ball_speed > Fixed::ZERO {
+            // Scale by the ratio; the product of two speeds would overflow `Fixed`
+            let ratio = speed / self.ball_speed;
+            self.ball_dx = self.ball_dx * ratio;
+            self.ball_dy = self.ball_dy * ratio;
+        }
+        self.ball_speed = speed;
+    }
+
+    /// Puts the ball back in the middle, at rest until it's served.
+    fn center_ball(&mut self) {
+        self.ball_x = Fixed::from_int((self.width / 2) as i32);
+        self.ball_y = Fixed::from_int((self.height / 2) as i32);
+        self.ball_dx = Fixed::ZERO;
+        self.ball_dy = Fixed::ZERO;
+        self.ball_speed = Fixed::from_int(self.physics.serve_speed);
+    }
+
+    /// Sends the ball off at the serve speed, away from the server at a random angle.
+    fn serve(&mut self) {
+        self.ball_speed = Fixed::from_int(self.physics.serve_speed);
+        let direction = match self.server {
+            Side::Left => 1,
+            Side::Right => -1,
+        };
+        let max_angle = self.physics.max_serve_angle;
+        let degrees = self.rng().range(-max_angle, max_angle);
+        self.launch(Fixed::from_degrees(degrees), direction);
+        self.emit(Event::Serve { side: self.server, dx: self.ball_dx.round(), dy: self.ball_dy.round() });
+    }
+
+    /// Restarts the random sequence from `seed`. The same seed and inputs replay the same
+    /// serves.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Pcg32::new(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The generator for the current match, kept apart for attract mode.
+    fn rng(&mut self) -> &mut Pcg32 {
+        match self.players {
+            Players::Demo => &mut self.demo_rng,
+            _ => &mut self.rng,
+        }
+    }
+
+    /// Reports `event` to the host, except in attract mode where nobody is playing.
+    fn emit(&self, event: Event) {
+        if self.players != Players::Demo {
+            (self.on_event)(event);
+        }
+    }
+
+    fn emit_wall_bounce(&self) {
+        self.emit(Event::WallBounce { x: self.ball_x.round(), dx: self.ball_dx.round(), dy: self.ball_dy.round() });
+    }
+
+    pub fn move_left_paddle(&mut self, up: bool) {
+        if matches!(self.game_mode, GameMode::GameOver | GameMode::Paused) {
+            return;
+        }
+        let move_amount = 25;
+        self.left_paddle = if up {
+            (self.left_paddle - move_amount).max(0)
+        } else {
+            (self.left_paddle + move_amount).min((self.height - self.paddle_height) as isize)
+        };
+    }
+
+    pub fn move_right_paddle(&mut self, up: bool) {
+        if matches!(self.game_mode, GameMode::GameOver | GameMode::Paused) {
+            return;
+        }
+        let move_amount = 25;
+        self.right_paddle = if up {
+            (self.right_paddle - move_amount).max(0)
+        } else {
+            (self.right_paddle + move_amount).min((self.height - self.paddle_height) as isize)
+        };
+    }
+
+    /// Handles a key press: W/S and I/K move the paddles, Esc or P pause, the menus are driven
+    /// with W/S, Enter, Esc and T. Any key stops attract mode.
+    pub fn handle_key(&mut self, key: char) -> Option<Request> {
+        self.idle_frames = 0;
+        match self.game_mode {
+            GameMode::Menu => return self.handle_menu_input(key),
+            GameMode::Settings => self.handle_settings_input(key),
+            _ if self.shows_playfield() && self.players == Players::Demo => self.set_mode(GameMode::Menu),
+            GameMode::Serve | GameMode::Playing | GameMode::PointScored => match key {
+                'w' => self.move_left_paddle(true),
+                's' => self.move_left_paddle(false),
+                'i' if self.players == Players::Two => self.move_right_paddle(true),
+                'k' if self.players == Players::Two => self.move_right_paddle(false),
+                '\x1b' | 'p' => self.pause(),
+                _ => {}
+            },
+            GameMode::Paused => return self.handle_pause_input(key),
+            GameMode::GameOver => {
+                if key == '\n' {
+                    self.set_mode(GameMode::Menu);
+                }
+            }
+        }
+        None
+    }
+
+    fn handle_menu_input(&mut self, key: char) -> Option<Request> {
+        match key {
+            'w' => self.selected_menu_item = self.selected_menu_item.saturating_sub(1),
+            's' if self.selected_menu_item < MENU_ITEMS.len() - 1 => self.selected_menu_item += 1,
+            // Esc jumps to Quit, so Esc then Enter leaves the game
+            '\x1b' => self.selected_menu_item = MENU_QUIT,
+            't' => self.theme = (self.theme + 1) % THEMES.len(),
+            '\n' => match self.selected_menu_item {
+                MENU_QUIT => return Some(Request::Quit),
+                MENU_SETTINGS => self.open_settings(),
+                0 => self.start_match(Players::One),
+                _ => self.start_match(Players::Two),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    fn start_match(&mut self, players: Players) {
+        self.players = players;
+        self.left_score = 0;
+        self.right_score = 0;
+        self.left_sets = 0;
+        self.right_sets = 0;
+        self.set_frames = 0;
+        self.winner = None;
+        self.stats = MatchStats::default();
+        let right = if players == Players::Demo { ATTRACT_DIFFICULTY } else { self.difficulty };
+        self.left_cpu = Ai::new(ATTRACT_DIFFICULTY.profile(), Side::Left, self.rng().next_u32() as u64);
+        self.right_cpu = Ai::new(right.profile(), Side::Right, self.rng().next_u32() as u64);
+        self.server = if self.rng().coin_flip() { Side::Left } else { Side::Right };
+        self.set_mode(GameMode::Serve);
+    }
+
+    fn pause(&mut self) {
+        self.paused = Some(self.game_mode);
+        self.game_mode = GameMode::Paused;
+        self.selected_pause_item = 0;
+    }
+
+    /// Goes back to the paused match, picking up a difficulty changed in the settings.
+    fn resume(&mut self) {
+        if let Some(mode) = self.paused.take() {
+            self.game_mode = mode;
+            self.right_cpu.profile = self.difficulty.profile();
+        }
+    }
+
+    fn handle_pause_input(&mut self, key: char) -> Option<Request> {
+        match key {
+            'w' => self.selected_pause_item = self.selected_pause_item.saturating_sub(1),
+            's' if self.selected_pause_item < PAUSE_ITEMS.len() - 1 => self.selected_pause_item += 1,
+            '\x1b' | 'p' => self.resume(),
+            '\n' => match self.selected_pause_item {
+                0 => self.resume(),
+                1 => {
+                    self.paused = None;
+                    self.start_match(self.players);
+                }
+                2 => self.open_settings(),
+                PAUSE_TO_MENU => {
+                    self.paused = None;
+                    self.set_mode(GameMode::Menu);
+                }
+                _ => return Some(Request::Quit),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    fn open_settings(&mut self) {
+        if self.paused.is_some() {
+            // Like pausing, leave the match's state and its timer as they are
+            self.game_mode = GameMode::Settings;
+        } else {
+            self.set_mode(GameMode::Settings);
+        }
+        self.selected_setting = 0;
+    }
+
+    /// W/S pick a rule and A/D change it; Enter changes it too, or leaves on BACK, as does Esc.
+    /// Leaving returns to the pause menu when the settings were opened from it.
+    fn handle_settings_input(&mut self, key: char) {
+        match key {
+            'w' => self.selected_setting = self.selected_setting.saturating_sub(1),
+            's' if self.selected_setting < SETTING_ROWS - 1 => self.selected_setting += 1,
+            'a' => self.change_setting(-1),
+            'd' => self.change_setting(1),
+            '\n' if self.selected_setting != SETTING_BACK => self.change_setting(1),
+            '\n' | '\x1b' if self.paused.is_some() => self.game_mode = GameMode::Paused,
+            '\n' | '\x1b' => self.set_mode(GameMode::Menu),
+            _ => {}
+        }
+    }
+
+    fn change_setting(&mut self, step: i32) {
+        let rules = &mut self.rules;
+        match self.selected_setting {
+            0 => rules.target_score = (rules.target_score as i32 + step).clamp(1, MAX_TARGET_SCORE as i32) as u8,
+            1 => rules.win_by_two = !rules.win_by_two,
+            2 => rules.time_limit = cycle(&TIME_LIMITS, rules.time_limit, step),
+            3 => rules.best_of = cycle(&SET_COUNTS, rules.best_of, step),
+            4 => rules.serve = cycle(&ServeRule::ALL, rules.serve, step),
+            5 => self.difficulty = cycle(&Difficulty::ALL, self.difficulty, step),
+            _ => {}
+        }
+    }
+
+    /// Text of a settings screen row.
+    fn setting_text(&self, row: usize) -> TextBuffer {
+        let rules = &self.rules;
+        match row {
+            0 => TextBuffer::format(format_args!("POINTS TO WIN: {}", rules.target_score)),
+            1 => TextBuffer::format(format_args!("WIN BY TWO: {}", if rules.win_by_two { "ON" } else { "OFF" })),
+            2 => match rules.time_limit {
+                Some(seconds) => TextBuffer::format(format_args!("TIME LIMIT: {}:{:02}", seconds / 60, seconds % 60)),
+                None => "TIME LIMIT: OFF".into(),
+            },
+            3 => TextBuffer::format(format_args!("SETS: BEST OF {}", rules.best_of)),
+            4 => TextBuffer::format(format_args!("SERVE: {}", rules.serve.name())),
+            5 => TextBuffer::format(format_args!("CPU: {}", self.difficulty.name())),
+            _ => "BACK".into(),
+        }
+    }
+
+    /// Repaints whatever changed since the previous call. Within a match only the old and new
+    /// positions of the paddles and the ball, plus the score line when it changes, are redrawn;
+    /// anything else (mode switch, menu selection) redraws the whole screen.
+    pub fn draw<R: Renderer>(&mut self, renderer: &mut R) {
+        let palette = THEMES[self.theme].packed(renderer);
+        let frame = self.drawn_frame();
+        renderer.begin_frame();
+
+        match self.last_frame.take() {
+            Some(last) if last == frame => {}
+            Some(last) if last.game_mode == frame.game_mode && self.shows_playfield() => {
+                if last.hud != frame.hud {
+                    self.repaint(renderer, &palette, self.hud_area());
+                }
+                if last.banner != frame.banner {
+                    self.repaint(renderer, &palette, self.banner_area());
+                }
+                for (old, new) in [
+                    (last.left_paddle, frame.left_paddle),
+                    (last.right_paddle, frame.right_paddle),
+                    (last.ball, frame.ball),
+                ] {
+                    if old != new {
+                        self.repaint(renderer, &palette, old);
+                        self.repaint(renderer, &palette, new);
+                    }
+                }
+            }
+            _ => {
+                renderer.clear_screen(palette.background);
+                self.draw_scene(renderer, &palette);
+            }
+        }
+
+        renderer.present();
+        self.last_frame = Some(frame);
+    }
+
+    /// Redraws the scene inside `area` only.
+    fn repaint<R: Renderer>(&self, renderer: &mut R, palette: &Theme<R::Pixel>, area: Rect) {
+        renderer.set_clip(Some(area));
+        renderer.fill_rect(area.x, area.y, area.width, area.height, palette.background);
+        self.draw_scene(renderer, palette);
+        renderer.set_clip(None);
+    }
+
+    fn draw_scene<R: Renderer>(&self, renderer: &mut R, palette: &Theme<R::Pixel>) {
+        match self.game_mode {
+            GameMode::Menu => self.draw_menu(renderer, palette),
+            GameMode::GameOver => {
+                if let Some(winner) = self.winner {
+                    renderer.draw_string_centered(self.height / 2 - 40, winner, TextStyle::Heading, palette.highlight);
+                }
+                renderer.draw_string_centered(self.height / 2, "GAME OVER", TextStyle::Regular, palette.alert);
+                if self.rules.best_of > 1 {
+                    renderer.draw_string_centered(self.height / 2 + 40, "SETS:", TextStyle::Regular, palette.foreground);
+                    let sets_text = TextBuffer::format(format_args!("{} - {}", self.left_sets, self.right_sets));
+                    renderer.draw_block_text_centered(self.height / 2 + 70, &sets_text, 6, palette.foreground);
+                    let last_set = TextBuffer::format(format_args!("LAST SET: {} - {}", self.left_score, self.right_score));
+                    renderer.draw_string_centered(self.height / 2 + 125, &last_set, TextStyle::Regular, palette.foreground);
+                } else {
+                    renderer.draw_string_centered(self.height / 2 + 40, "FINAL SCORE:", TextStyle::Regular, palette.foreground);
+                    let score_text = TextBuffer::format(format_args!("{} - {}", self.left_score, self.right_score));
+                    renderer.draw_block_text_centered(self.height / 2 + 70, &score_text, 6, palette.foreground);
+                }
+
+                // Match stats
+                let stats = &self.stats;
+                let average = stats.average_rally_tenths();
+                let seconds = stats.seconds(self.frame_rate);
+                let lines = [
+                    TextBuffer::format(format_args!("LONGEST RALLY: {} HITS", stats.longest_rally)),
+                    TextBuffer::format(format_args!("AVERAGE RALLY: {}.{} HITS", average / 10, average % 10)),
+                    TextBuffer::format(format_args!("MATCH TIME: {}:{:02}", seconds / 60, seconds % 60)),
+                ];
+                for (i, line) in lines.iter().enumerate() {
+                    renderer.draw_string_centered(self.height / 2 + 155 + i * 20, line, TextStyle::Regular, palette.accent);
+                }
+                renderer.draw_string_centered(self.height / 2 + 230, "PRESS ENTER TO RETURN TO MENU", TextStyle::Regular, palette.info);
+            }
+            GameMode::Settings => {
+                renderer.draw_string_centered(self.height / 2 - 120, "SETTINGS", TextStyle::Title, palette.foreground);
+                for row in 0..SETTING_ROWS {
+                    let text = self.setting_text(row);
+                    let (text, style) = if self.selected_setting == row {
+                        (TextBuffer::format(format_args!("> {text} <")), TextStyle::Bold)
+                    } else {
+                        (TextBuffer::format(format_args!("  {text}  ")), TextStyle::Regular)
+                    };
+                    renderer.draw_string_centered(self.height / 2 - 50 + row * 24, &text, style, palette.foreground);
+                }
+                renderer.draw_string_centered(self.height / 2 + 130, "W/S TO SELECT, A/D TO CHANGE", TextStyle::Regular, palette.accent);
+                renderer.draw_string_centered(self.height / 2 + 150, "ENTER ON BACK OR ESC TO RETURN", TextStyle::Regular, palette.accent);
+            }
+            GameMode::Paused => {
+                self.draw_playfield(renderer, palette);
+                renderer.fill_rect_blend(0, 0, self.width, self.height, THEMES[self.theme].background, PAUSE_DIM);
+                renderer.draw_string_centered(self.height / 2 - 80, "PAUSED", TextStyle::Title, palette.foreground);
+                for (i, item) in PAUSE_ITEMS.iter().enumerate() {
+                    let (text, style) = if self.selected_pause_item == i {
+                        (TextBuffer::format(format_args!("> {item} <")), TextStyle::Bold)
+                    } else {
+                        (TextBuffer::format(format_args!("  {item}  ")), TextStyle::Regular)
+                    };
+                    renderer.draw_string_centered(self.height / 2 - 20 + i * 24, &text, style, palette.foreground);
+                }
+                renderer.draw_string_centered(self.height / 2 + 100, "W/S TO SELECT, ENTER TO CHOOSE", TextStyle::Regular, palette.accent);
+                renderer.draw_string_centered(self.height / 2 + 120, "ESC OR P TO RESUME", TextStyle::Regular, palette.accent);
+            }
+            GameMode::Serve | GameMode::Playing | GameMode::PointScored => {
+                self.draw_playfield(renderer, palette);
+                if self.players == Players::Demo {
+                    self.draw_menu(renderer, palette);
+                    renderer.draw_string_centered(self.height / 2 + 200, "DEMO - PRESS ANY KEY", TextStyle::Bold, palette.highlight);
+                } else if let Some(banner) = self.banner() {
+                    let area = self.banner_area();
+                    renderer.draw_block_text_centered(area.y, &banner, BANNER_SCALE, palette.highlight);
+                    if self.game_mode == GameMode::Serve {
+                        let serving = TextBuffer::format(format_args!("{} SERVES", self.player_name(self.server)));
+                        renderer.draw_string_centered(area.bottom() - 20, &serving, TextStyle::Regular, palette.info);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_menu<R: Renderer>(&self, renderer: &mut R, palette: &Theme<R::Pixel>) {
+        renderer.draw_string_centered(self.height / 2 - 80, "ULTRA PONG", TextStyle::Title, palette.foreground);
+        for (i, item) in MENU_ITEMS.iter().enumerate() {
+            let (text, font) = if self.selected_menu_item == i {
+                (TextBuffer::format(format_args!("> {item} <")), TextStyle::Bold)
+            } else {
+                (TextBuffer::format(format_args!("  {item}  ")), TextStyle::Regular)
+            };
+            renderer.draw_string_centered(self.height / 2 - 20 + i * 20, &text, font, palette.foreground);
+        }
+        renderer.draw_string_centered(self.height / 2 + 60, "CONTROLS:", TextStyle::Regular, palette.accent);
+        renderer.draw_string_centered(self.height / 2 + 80, "PLAYER 1: W/S KEYS", TextStyle::Regular, palette.player_one);
+        renderer.draw_string_centered(self.height / 2 + 100, "PLAYER 2: I/K KEYS", TextStyle::Regular, palette.player_two);
+        let mut rules_text = TextBuffer::new();
+        let _ = self.rules.write_summary(&mut rules_text);
+        let _ = rules_text.write_str(" WINS!");
+        renderer.draw_string_centered(self.height / 2 + 140, &rules_text, TextStyle::Regular, palette.highlight);
+        renderer.draw_string_centered(self.height / 2 + 160, "MENU: W/S TO SELECT", TextStyle::Regular, palette.alert);
+        renderer.draw_string_centered(self.height / 2 + 180, "ENTER TO START, ESC TO QUIT", TextStyle::Regular, palette.accent);
+        let theme_text = TextBuffer::format(format_args!("THEME: {} (T TO CHANGE)", palette.name));
+        renderer.draw_string_centered(self.height / 2 + 220, &theme_text, TextStyle::Regular, palette.info);
+    }
+
+    fn draw_playfield<R: Renderer>(&self, renderer: &mut R, palette: &Theme<R::Pixel>) {
+        // Draw paddles
+        let left_paddle = self.left_paddle_rect();
+        let right_paddle = self.right_paddle_rect();
+        renderer.fill_rect(left_paddle.x, left_paddle.y, left_paddle.width, left_paddle.height, palette.foreground);
+        renderer.fill_rect(right_paddle.x, right_paddle.y, right_paddle.width, right_paddle.height, palette.foreground);
+
+        // Draw ball
+        let ball = self.ball_rect();
+        renderer.fill_rect(ball.x, ball.y, ball.width, ball.height, palette.foreground);
+
+        // Draw center line
+        for y in (0..self.height).step_by(20) {
+            renderer.draw_vline(self.width / 2, y, 10, palette.center_line);
+        }
+
+        // Draw scores either side of the center line
+        let left_score = TextBuffer::format(format_args!("{}", self.left_score));
+        let right_score = TextBuffer::format(format_args!("{}", self.right_score));
+        let left_width = block_font::text_width(&left_score, SCORE_SCALE);
+        renderer.draw_block_text(self.width / 2 - SCORE_GAP - left_width, 20, &left_score, SCORE_SCALE, palette.foreground);
+        renderer.draw_block_text(self.width / 2 + SCORE_GAP, 20, &right_score, SCORE_SCALE, palette.foreground);
+
+        // Draw speed indicator
+        let speed_text = TextBuffer::format(format_args!("SPEED: {}/{}", self.ball_speed(), self.physics.max_speed));
+        renderer.draw_string(10, 10, &speed_text, TextStyle::Regular, palette.accent);
+
+        // Draw set score and clock, when the rules use them
+        if self.rules.best_of > 1 {
+            let sets_text = TextBuffer::format(format_args!("SETS: {} - {}", self.left_sets, self.right_sets));
+            renderer.draw_string(10, 30, &sets_text, TextStyle::Regular, palette.accent);
+        }
+        if let Some(seconds) = self.seconds_left() {
+            let clock_text = match seconds {
+                0 => "SUDDEN DEATH".into(),
+                _ => TextBuffer::format(format_args!("TIME: {}:{:02}", seconds / 60, seconds % 60)),
+            };
+            renderer.draw_string(10, 50, &clock_text, TextStyle::Regular, palette.alert);
+        }
+    }
+
+    /// Whether a match between people is on, not counting pauses.
+    pub fn is_playing(&self) -> bool {
+        self.shows_playfield() && self.players != Players::Demo
+    }
+
+    /// Whether a match, or the attract mode demo, is on.
+    fn shows_playfield(&self) -> bool {
+        matches!(self.game_mode, GameMode::Serve | GameMode::Playing | GameMode::PointScored)
+    }
+
+    /// Ball speed in pixels per second, as shown in the HUD.
+    pub fn ball_speed(&self) -> i32 {
+        self.ball_speed.round()
+    }
+
+    fn left_paddle_rect(&self) -> Rect {
+        Rect::new(0, self.left_paddle.max(0) as usize, self.paddle_width, self.paddle_height)
+    }
+
+    fn right_paddle_rect(&self) -> Rect {
+        Rect::new(self.width - self.paddle_width, self.right_paddle.max(0) as usize, self.paddle_width, self.paddle_height)
+    }
+
+    /// The visible part of the ball, which may be partly off the side of the screen.
+    fn ball_rect(&self) -> Rect {
+        let left = self.ball_x.round().clamp(0, self.width as i32);
+        let right = (self.ball_x.round() + self.ball_size as i32).clamp(0, self.width as i32);
+        Rect::new(left as usize, self.ball_y.round().max(0) as usize, (right - left) as usize, self.ball_size)
+    }
+
+    /// The big text over the playfield: the serve countdown, then "POINT!" blinking after a
+    /// point. Attract mode shows the menu instead.
+    fn banner(&self) -> Option<TextBuffer> {
+        if self.players == Players::Demo {
+            return None;
+        }
+        match self.game_mode {
+            GameMode::Serve => Some(TextBuffer::format(format_args!("{}", SERVE_COUNTDOWN - self.mode_frames / self.frame_rate))),
+            GameMode::PointScored if (self.mode_frames * POINT_FLASH_HZ * 2 / self.frame_rate).is_multiple_of(2) => Some("POINT!".into()),
+            _ => None,
+        }
+    }
+
+    /// The band above the middle of the field holding the banner and, while serving, who
+    /// serves. It stays clear of the ball waiting to be served.
+    fn banner_area(&self) -> Rect {
+        let top = self.height / 2 - 110;
+        Rect::new(0, top, self.width, block_font::GLYPH_HEIGHT * BANNER_SCALE + 34)
+    }
+
+    fn player_name(&self, side: Side) -> &'static str {
+        match (side, self.players) {
+            (Side::Left, _) => "PLAYER 1",
+            (Side::Right, Players::One) => "CPU",
+            (Side::Right, _) => "PLAYER 2",
+        }
+    }
+
+    /// The band at the top of the playfield holding the score and speed text.
+    fn hud_area(&self) -> Rect {
+        Rect::new(0, 0, self.width, 20 + block_font::GLYPH_HEIGHT * SCORE_SCALE)
+    }
+
+    fn drawn_frame(&self) -> DrawnFrame {
+        DrawnFrame {
+            game_mode: self.game_mode,
+            players: self.players,
+            selected_menu_item: self.selected_menu_item,
+            selected_setting: self.selected_setting,
+            selected_pause_item: self.selected_pause_item,
+            rules: self.rules,
+            difficulty: self.difficulty,
+            theme: self.theme,
+            left_paddle: self.left_paddle_rect(),
+            right_paddle: self.right_paddle_rect(),
+            ball: self.ball_rect(),
+            hud: Hud {
+                score: (self.left_score, self.right_score),
+                sets: (self.left_sets, self.right_sets),
+                speed: self.ball_speed(),
+                seconds_left: self.seconds_left(),
+            },
+            banner: self.banner(),
+        }
+    }
+}
+
+/// The option `step` places after `current` in `options`, wrapping around at either end.
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: i32) -> T {
+    let index = options.iter().position(|&option| option == current).unwrap_or(0) as i32;
+    options[(index + step).rem_euclid(options.len() as i32) as usize]
+}
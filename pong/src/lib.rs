@@ -0,0 +1,25 @@
+//! Pong game logic, kept free of hardware access so it builds for the kernel and for the
+//! host, where `cargo test -p pong` runs its tests.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod ai;
+pub mod block_font;
+pub mod collision;
+pub mod event;
+pub mod fixed;
+mod game;
+pub mod render;
+pub mod rng;
+pub mod rules;
+pub mod stats;
+pub mod theme;
+
+pub use ai::Difficulty;
+pub use event::{Event, Side};
+pub use game::{GameMode, Physics, Players, PongGame, Request};
+pub use render::Renderer;
+pub use rules::MatchRules;
+pub use stats::MatchStats;
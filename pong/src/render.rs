@@ -0,0 +1,215 @@
+//! What the game needs from a display. The kernel implements `Renderer` for its framebuffer
+//! `ScreenWriter`; tests can implement it to record draw calls instead.
+
+use core::fmt::{self, Write};
+use core::ops::Deref;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn right(&self) -> usize {
+        self.x.saturating_add(self.width)
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Smallest rectangle covering both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+
+    /// Overlapping part of both, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if x < right && y < bottom {
+            Some(Rect::new(x, y, right - x, bottom - y))
+        } else {
+            None
+        }
+    }
+
+    /// True if the rectangles overlap or share an edge, i.e. their union wastes no area
+    /// along that edge.
+    pub fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right() && other.x <= self.right() && self.y <= other.bottom() && other.y <= self.bottom()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+    pub const DARK_GRAY: Color = Color::rgb(0x55, 0x55, 0x55);
+    pub const LIGHT_GRAY: Color = Color::rgb(0xaa, 0xaa, 0xaa);
+    pub const RED: Color = Color::rgb(0xff, 0x55, 0x55);
+    pub const GREEN: Color = Color::rgb(0x55, 0xff, 0x55);
+    pub const YELLOW: Color = Color::rgb(0xff, 0xff, 0x55);
+    pub const CYAN: Color = Color::rgb(0x55, 0xff, 0xff);
+    pub const LIGHT_BLUE: Color = Color::rgb(0xaa, 0xaa, 0xff);
+    pub const LIGHT_RED: Color = Color::rgb(0xff, 0xaa, 0xaa);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Mixes this colour over `background` with the given opacity, 255 being opaque.
+    pub fn blend_over(self, background: Color, alpha: u8) -> Color {
+        let mix = |fg: u8, bg: u8| {
+            let alpha = alpha as u16;
+            ((fg as u16 * alpha + bg as u16 * (255 - alpha) + 127) / 255) as u8
+        };
+        Color::rgb(mix(self.r, background.r), mix(self.g, background.g), mix(self.b, background.b))
+    }
+}
+
+/// Text sizes the game uses, from the body text up to the title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    Regular,
+    Bold,
+    Heading,
+    Title,
+}
+
+/// Drawing operations the game's scenes are made of. Coordinates are in pixels from the
+/// top-left corner; drawing outside the display or the clip rectangle is ignored.
+pub trait Renderer {
+    /// A colour converted to the display's native format, see `Theme::packed`.
+    type Pixel: Copy;
+
+    fn pixel(&self, color: Color) -> Self::Pixel;
+
+    /// Brackets the drawing of one frame, which becomes visible at `present`.
+    fn begin_frame(&mut self);
+    fn present(&mut self);
+
+    /// Restricts drawing to `clip`, or lifts the restriction with `None`.
+    fn set_clip(&mut self, clip: Option<Rect>);
+
+    fn clear_screen(&mut self, color: Self::Pixel);
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Self::Pixel);
+    /// Blends `color` over a rectangle with opacity `alpha` (0-255), e.g. to dim what is behind
+    /// an overlay.
+    fn fill_rect_blend(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color, alpha: u8);
+    fn draw_vline(&mut self, x: usize, y: usize, length: usize, color: Self::Pixel);
+    fn draw_string(&mut self, x: usize, y: usize, text: &str, style: TextStyle, color: Self::Pixel);
+    fn draw_string_centered(&mut self, y: usize, text: &str, style: TextStyle, color: Self::Pixel);
+    /// Draws `text` in the block font (see `block_font`) with `scale`-pixel squares.
+    fn draw_block_text(&mut self, x: usize, y: usize, text: &str, scale: usize, color: Self::Pixel);
+    fn draw_block_text_centered(&mut self, y: usize, text: &str, scale: usize, color: Self::Pixel);
+}
+
+/// A line of text formatted on the stack, so drawing a frame never touches the heap, which
+/// in the kernel is never given back. Text past `CAPACITY` bytes is cut off.
+#[derive(Clone, Copy)]
+pub struct TextBuffer {
+    bytes: [u8; TextBuffer::CAPACITY],
+    len: usize,
+}
+
+impl TextBuffer {
+    pub const CAPACITY: usize = 64;
+
+    pub const fn new() -> Self {
+        TextBuffer { bytes: [0; TextBuffer::CAPACITY], len: 0 }
+    }
+
+    /// Formats `args`, e.g. `TextBuffer::format(format_args!("{} - {}", left, right))`.
+    pub fn format(args: fmt::Arguments) -> Self {
+        let mut text = TextBuffer::new();
+        // Running out of room only cuts the text short
+        let _ = text.write_fmt(args);
+        text
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever copied in, see `write_str`
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+impl Default for TextBuffer {
+    fn default() -> Self {
+        TextBuffer::new()
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> Self {
+        let mut buffer = TextBuffer::new();
+        let _ = buffer.write_str(text);
+        buffer
+    }
+}
+
+impl Write for TextBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = TextBuffer::CAPACITY - self.len;
+        let mut end = s.len().min(room);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.bytes[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        if end < s.len() { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+impl Deref for TextBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for TextBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for TextBuffer {}
+
+impl fmt::Debug for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self)
+    }
+}
@@ -0,0 +1,59 @@
+//! PCG32 pseudo-random numbers (O'Neill, "PCG: A Family of Simple Fast Space-Efficient
+//! Statistically Good Algorithms for Random Number Generation"). The game never reads
+//! entropy itself: the host picks the seed, so a seed noted down replays the same match.
+
+const MULTIPLIER: u64 = 6364136223846793005;
+/// Stream used by `Pcg32::new`; any odd increment gives an independent sequence.
+const DEFAULT_STREAM: u64 = 0xda3e39cb94b95bdb;
+
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, DEFAULT_STREAM)
+    }
+
+    /// Generator for `seed` on one of 2^63 streams, like the reference `pcg32_srandom_r`.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 { state: 0, increment: (stream << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Uniform in `0..bound`, without the bias of a plain `% bound`. `bound` must not be 0.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        // Reject the lowest 2^32 % bound values so the rest divide evenly into `bound` buckets
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u32();
+            if value >= threshold {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Uniform in `low..=high`; `low` must not be greater than `high`.
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        let span = high.abs_diff(low).wrapping_add(1);
+        // A span of 0 means the full range of i32
+        let offset = if span == 0 { self.next_u32() } else { self.below(span) };
+        low.wrapping_add_unsigned(offset)
+    }
+
+    pub fn coin_flip(&mut self) -> bool {
+        self.next_u32() & 1 == 0
+    }
+}
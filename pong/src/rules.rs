@@ -0,0 +1,108 @@
+//! How a match is played: points per set, deuce, an optional clock, the number of sets and
+//! who serves.
+
+use alloc::string::String;
+use core::fmt;
+use crate::event::Side;
+
+/// Time limits offered on the settings screen, in seconds.
+pub const TIME_LIMITS: [Option<u16>; 5] = [None, Some(60), Some(120), Some(180), Some(300)];
+/// Match lengths offered on the settings screen, as best of N sets.
+pub const SET_COUNTS: [u8; 4] = [1, 3, 5, 7];
+pub const MAX_TARGET_SCORE: u8 = 21;
+
+/// Who serves after a point. The first serve of a match goes to a random side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeRule {
+    /// The player who lost the point.
+    Loser,
+    /// The players take turns.
+    Alternate,
+}
+
+impl ServeRule {
+    pub const ALL: [ServeRule; 2] = [ServeRule::Loser, ServeRule::Alternate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ServeRule::Loser => "LOSER",
+            ServeRule::Alternate => "ALTERNATE",
+        }
+    }
+
+    /// Who serves next, after `scorer` won a point served by `server`.
+    pub fn next_server(self, server: Side, scorer: Side) -> Side {
+        match self {
+            ServeRule::Loser => scorer.opponent(),
+            ServeRule::Alternate => server.opponent(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchRules {
+    /// Points that win a set, from 1 to `MAX_TARGET_SCORE`.
+    pub target_score: u8,
+    /// At the target score a player also needs a two point lead, so 10-10 goes on to 12-10.
+    pub win_by_two: bool,
+    /// Length of each set in seconds. When time runs out the leader wins the set; a tied set
+    /// goes to sudden death, where the next point wins.
+    pub time_limit: Option<u16>,
+    /// The match is best of this many sets, an odd number.
+    pub best_of: u8,
+    pub serve: ServeRule,
+}
+
+impl MatchRules {
+    pub const DEFAULT: MatchRules = MatchRules {
+        target_score: 3,
+        win_by_two: false,
+        time_limit: None,
+        best_of: 1,
+        serve: ServeRule::Loser,
+    };
+
+    /// Who has won a set at `left`-`right`, given whether its time has run out.
+    pub fn set_winner(&self, left: u8, right: u8, time_up: bool) -> Option<Side> {
+        let (leader, lead, top) = match left.cmp(&right) {
+            core::cmp::Ordering::Greater => (Side::Left, left - right, left),
+            core::cmp::Ordering::Less => (Side::Right, right - left, right),
+            core::cmp::Ordering::Equal => return None,
+        };
+        let reached_target = top >= self.target_score && (!self.win_by_two || lead >= 2);
+        (reached_target || time_up).then_some(leader)
+    }
+
+    /// Sets a player needs to win the match.
+    pub fn sets_to_win(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+
+    /// One line for the menu, e.g. "FIRST TO 11, WIN BY 2, BEST OF 3".
+    pub fn summary(&self) -> String {
+        let mut text = String::new();
+        let _ = self.write_summary(&mut text);
+        text
+    }
+
+    /// Writes the `summary` line to `out`, which lets it be drawn without allocating.
+    pub fn write_summary(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        match self.time_limit {
+            Some(seconds) => write!(out, "{} MIN SETS, FIRST TO {}", seconds / 60, self.target_score)?,
+            None => write!(out, "FIRST TO {} POINTS", self.target_score)?,
+        }
+        if self.win_by_two {
+            out.write_str(", WIN BY 2")?;
+        }
+        if self.best_of > 1 {
+            write!(out, ", BEST OF {}", self.best_of)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules::DEFAULT
+    }
+}
@@ -0,0 +1,38 @@
+//! Numbers kept over a match for the results screen.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// Rallies played out, from serve to point.
+    pub rallies: u32,
+    /// Paddle hits over the whole match.
+    pub hits: u32,
+    /// Most paddle hits in one rally.
+    pub longest_rally: u32,
+    /// Paddle hits in the rally being played.
+    pub rally_hits: u32,
+    /// Frames the match has run for, not counting pauses.
+    pub frames: u32,
+}
+
+impl MatchStats {
+    pub fn hit(&mut self) {
+        self.rally_hits += 1;
+    }
+
+    pub fn end_rally(&mut self) {
+        self.rallies += 1;
+        self.hits += self.rally_hits;
+        self.longest_rally = self.longest_rally.max(self.rally_hits);
+        self.rally_hits = 0;
+    }
+
+    /// Mean hits per rally, in tenths.
+    pub fn average_rally_tenths(&self) -> u32 {
+        (self.hits * 10).checked_div(self.rallies).unwrap_or(0)
+    }
+
+    /// Length of the match in whole seconds.
+    pub fn seconds(&self, frame_rate: u32) -> u32 {
+        self.frames / frame_rate
+    }
+}
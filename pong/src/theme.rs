@@ -0,0 +1,77 @@
+use crate::render::{Color, Renderer};
+
+/// Colours for every role on the Pong screens.
+///
+/// Themes are written as `Theme<Color>`; `packed()` converts one to the renderer's native
+/// pixel format so drawing a frame never has to convert colours.
+#[derive(Clone, Copy)]
+pub struct Theme<C> {
+    pub name: &'static str,
+    pub background: C,
+    pub foreground: C,
+    pub center_line: C,
+    pub accent: C,
+    pub highlight: C,
+    pub alert: C,
+    pub info: C,
+    pub player_one: C,
+    pub player_two: C,
+}
+
+impl Theme<Color> {
+    pub fn packed<R: Renderer>(&self, renderer: &R) -> Theme<R::Pixel> {
+        Theme {
+            name: self.name,
+            background: renderer.pixel(self.background),
+            foreground: renderer.pixel(self.foreground),
+            center_line: renderer.pixel(self.center_line),
+            accent: renderer.pixel(self.accent),
+            highlight: renderer.pixel(self.highlight),
+            alert: renderer.pixel(self.alert),
+            info: renderer.pixel(self.info),
+            player_one: renderer.pixel(self.player_one),
+            player_two: renderer.pixel(self.player_two),
+        }
+    }
+}
+
+pub const CLASSIC: Theme<Color> = Theme {
+    name: "CLASSIC",
+    background: Color::BLACK,
+    foreground: Color::WHITE,
+    center_line: Color::DARK_GRAY,
+    accent: Color::GREEN,
+    highlight: Color::YELLOW,
+    alert: Color::RED,
+    info: Color::CYAN,
+    player_one: Color::LIGHT_BLUE,
+    player_two: Color::LIGHT_RED,
+};
+
+pub const AMBER: Theme<Color> = Theme {
+    name: "AMBER",
+    background: Color::rgb(0x10, 0x08, 0x00),
+    foreground: Color::rgb(0xff, 0xb0, 0x00),
+    center_line: Color::rgb(0x80, 0x58, 0x00),
+    accent: Color::rgb(0xff, 0xcc, 0x40),
+    highlight: Color::rgb(0xff, 0xe0, 0x80),
+    alert: Color::rgb(0xff, 0x80, 0x00),
+    info: Color::rgb(0xff, 0xc8, 0x60),
+    player_one: Color::rgb(0xff, 0xb0, 0x00),
+    player_two: Color::rgb(0xe0, 0x90, 0x00),
+};
+
+pub const PHOSPHOR: Theme<Color> = Theme {
+    name: "PHOSPHOR",
+    background: Color::rgb(0x00, 0x10, 0x00),
+    foreground: Color::rgb(0x33, 0xff, 0x66),
+    center_line: Color::rgb(0x11, 0x66, 0x22),
+    accent: Color::rgb(0x99, 0xff, 0x99),
+    highlight: Color::rgb(0xcc, 0xff, 0xcc),
+    alert: Color::rgb(0x22, 0xcc, 0x44),
+    info: Color::rgb(0x66, 0xff, 0x99),
+    player_one: Color::rgb(0x33, 0xff, 0x66),
+    player_two: Color::rgb(0x22, 0xdd, 0x55),
+};
+
+pub const THEMES: [Theme<Color>; 3] = [CLASSIC, AMBER, PHOSPHOR];
@@ -0,0 +1,96 @@
+use pong::ai::{Ai, AiProfile, AiView, Difficulty, predict_intercept};
+use pong::fixed::Fixed;
+use pong::Side;
+
+const FRAME_RATE: u32 = 60;
+
+/// A ball at (`x`, `y`) moving at (`dx`, `dy`) on an 800x600 field, with the right paddle's
+/// face at x = 780.
+fn view(x: i32, y: i32, dx: i32, dy: i32) -> AiView {
+    AiView {
+        ball_x: Fixed::from_int(x),
+        ball_y: Fixed::from_int(y),
+        ball_dx: Fixed::from_int(dx),
+        ball_dy: Fixed::from_int(dy),
+        ball_size: 10,
+        contact_x: Fixed::from_int(770),
+        paddle_y: 250,
+        opponent_y: 250,
+        paddle_height: 100,
+        field_height: 600,
+        frame_rate: FRAME_RATE,
+    }
+}
+
+fn profile(reaction_ms: u32, speed: i32, aim: i32) -> AiProfile {
+    AiProfile { reaction_ms, speed, aim_error: 0, aim }
+}
+
+#[test]
+fn predicts_a_straight_path() {
+    assert_eq!(predict_intercept(&view(370, 100, 400, 100)).round(), 200);
+}
+
+#[test]
+fn predicts_bounces_off_both_walls() {
+    // The ball's top can reach 590. Unfolded it would end at 1100, 510 past the bottom wall
+    assert_eq!(predict_intercept(&view(270, 100, 500, 1000)).round(), 80);
+    // 1600 is off the bottom, then the top, then 420 down again
+    assert_eq!(predict_intercept(&view(270, 100, 500, 1500)).round(), 420);
+    assert_eq!(predict_intercept(&view(570, 50, 200, -100)).round(), 50);
+}
+
+#[test]
+fn waits_out_its_reaction_time() {
+    let mut ai = Ai::new(profile(100, 600, 0), Side::Right, 1);
+    let view = view(400, 500, 300, 0);
+    for _ in 0..6 {
+        assert_eq!(ai.step(&view), 250);
+    }
+    assert!(ai.step(&view) > 250);
+}
+
+#[test]
+fn paddle_speed_is_limited() {
+    let mut ai = Ai::new(profile(0, 600, 0), Side::Right, 1);
+    assert_eq!(ai.step(&view(400, 500, 300, 0)), 260);
+}
+
+#[test]
+fn returns_to_the_middle_when_the_ball_goes_away() {
+    let mut ai = Ai::new(profile(0, 600, 0), Side::Right, 1);
+    let mut view = view(400, 500, -300, 0);
+    view.paddle_y = 0;
+    assert_eq!(ai.step(&view), 10);
+}
+
+#[test]
+fn impossible_cpu_reaches_the_intercept() {
+    let mut ai = Ai::new(Difficulty::Impossible.profile(), Side::Right, 1);
+    let mut view = view(370, 100, 400, 100);
+    view.opponent_y = 250;
+    let paddle = ai.step(&view);
+    // The ball crosses at 200..210 and must land on the paddle
+    assert!(paddle <= 200 && paddle + 100 >= 210, "paddle at {paddle}");
+}
+
+#[test]
+fn aims_away_from_the_opponent() {
+    let mut view = view(400, 300, 300, 0);
+    view.opponent_y = 0;
+    let mut ai = Ai::new(profile(0, 3000, 50), Side::Right, 1);
+    // Opponent at the top: meet the ball below center to send it down
+    assert_eq!(ai.step(&view), 305 - 50 - 25);
+
+    view.opponent_y = 500;
+    let mut ai = Ai::new(profile(0, 3000, 50), Side::Right, 1);
+    assert_eq!(ai.step(&view), 305 - 50 + 25);
+}
+
+#[test]
+fn left_side_reacts_to_balls_moving_left() {
+    let mut ai = Ai::new(profile(0, 600, 0), Side::Left, 1);
+    let mut view = view(400, 500, -300, 0);
+    view.contact_x = Fixed::from_int(20);
+    assert_eq!(ai.step(&view), 260);
+}
@@ -0,0 +1,115 @@
+//! The kernel's heap never frees, so drawing a frame must not allocate. A counting global
+//! allocator checks every screen.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use pong::render::{Color, Rect, Renderer, TextStyle};
+use pong::{GameMode, MatchRules, Players, PongGame};
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Draws nowhere.
+struct Discard;
+
+impl Renderer for Discard {
+    type Pixel = Color;
+
+    fn pixel(&self, color: Color) -> Color {
+        color
+    }
+
+    fn begin_frame(&mut self) {}
+
+    fn present(&mut self) {}
+
+    fn set_clip(&mut self, _clip: Option<Rect>) {}
+
+    fn clear_screen(&mut self, _color: Color) {}
+
+    fn fill_rect(&mut self, _x: usize, _y: usize, _width: usize, _height: usize, _color: Color) {}
+
+    fn fill_rect_blend(&mut self, _x: usize, _y: usize, _width: usize, _height: usize, _color: Color, _alpha: u8) {}
+
+    fn draw_vline(&mut self, _x: usize, _y: usize, _length: usize, _color: Color) {}
+
+    fn draw_string(&mut self, _x: usize, _y: usize, _text: &str, _style: TextStyle, _color: Color) {}
+
+    fn draw_string_centered(&mut self, _y: usize, _text: &str, _style: TextStyle, _color: Color) {}
+
+    fn draw_block_text(&mut self, _x: usize, _y: usize, _text: &str, _scale: usize, _color: Color) {}
+
+    fn draw_block_text_centered(&mut self, _y: usize, _text: &str, _scale: usize, _color: Color) {}
+}
+
+/// Runs `frames` frames, returning how many allocations `draw` made.
+fn draw_allocations(game: &mut PongGame, frames: u32) -> usize {
+    let mut allocations = 0;
+    for _ in 0..frames {
+        game.update();
+        let before = ALLOCATIONS.with(Cell::get);
+        game.draw(&mut Discard);
+        allocations += ALLOCATIONS.with(Cell::get) - before;
+    }
+    allocations
+}
+
+#[test]
+fn drawing_never_allocates() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.rules = MatchRules { time_limit: Some(60), best_of: 3, ..MatchRules::DEFAULT };
+    assert_eq!(draw_allocations(&mut game, 60), 0, "menu");
+
+    // Attract mode plays behind the menu
+    assert_eq!(draw_allocations(&mut game, 60 * 30), 0, "attract mode");
+    assert_eq!(game.players, Players::Demo);
+    game.handle_key(' ');
+
+    game.handle_key('s');
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Settings);
+    for row in 0..7 {
+        game.handle_key(if row == 0 { 'w' } else { 's' });
+        assert_eq!(draw_allocations(&mut game, 2), 0, "settings row {row}");
+    }
+    game.handle_key('\x1b');
+
+    game.handle_key('w');
+    game.handle_key('w');
+    game.handle_key('\n');
+    assert_eq!(game.players, Players::One);
+    assert_eq!(draw_allocations(&mut game, 60 * 10), 0, "match");
+
+    game.handle_key('p');
+    assert_eq!(game.game_mode, GameMode::Paused);
+    for _ in 0..5 {
+        assert_eq!(draw_allocations(&mut game, 2), 0, "pause menu");
+        game.handle_key('s');
+    }
+    game.handle_key('p');
+
+    game.right_sets = 1;
+    game.right_score = 3;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    assert_eq!(draw_allocations(&mut game, 60), 0, "results");
+}
@@ -0,0 +1,74 @@
+use pong::collision::{sweep, Aabb, Face};
+use pong::fixed::Fixed;
+
+fn px(value: i32) -> Fixed {
+    Fixed::from_int(value)
+}
+
+fn square(x: i32, y: i32, size: i32) -> Aabb {
+    Aabb::new(px(x), px(y), px(size), px(size))
+}
+
+fn paddle() -> Aabb {
+    Aabb::new(px(0), px(100), px(15), px(80))
+}
+
+#[test]
+fn head_on_hit_stops_at_the_face() {
+    let hit = sweep(square(40, 120, 15), px(-100), px(0), paddle()).unwrap();
+    assert_eq!(hit.face, Face::Right);
+    assert_eq!(hit.time, Fixed::from_ratio(1, 4));
+    assert_eq!((hit.x, hit.y), (px(15), px(120)));
+}
+
+#[test]
+fn moving_down_onto_the_top_edge() {
+    let hit = sweep(square(5, 70, 15), px(-2), px(30), paddle()).unwrap();
+    assert_eq!(hit.face, Face::Top);
+    assert_eq!(hit.time, Fixed::from_ratio(1, 2));
+    assert_eq!(hit.y, px(85));
+}
+
+#[test]
+fn moving_up_onto_the_bottom_edge() {
+    let hit = sweep(square(0, 190, 15), px(0), px(-20), paddle()).unwrap();
+    assert_eq!(hit.face, Face::Bottom);
+    assert_eq!(hit.y, px(180));
+}
+
+#[test]
+fn passing_above_misses() {
+    assert_eq!(sweep(square(40, 10, 15), px(-100), px(0), paddle()), None);
+}
+
+#[test]
+fn stopping_short_misses() {
+    assert_eq!(sweep(square(40, 120, 15), px(-10), px(0), paddle()), None);
+    // Ending the move exactly against the face isn't a hit yet
+    assert_eq!(sweep(square(40, 120, 15), px(-25), px(0), paddle()), None);
+}
+
+#[test]
+fn touching_and_moving_away_misses() {
+    assert_eq!(sweep(square(15, 120, 15), px(10), px(0), paddle()), None);
+}
+
+#[test]
+fn sliding_past_a_corner_misses() {
+    assert_eq!(sweep(square(40, 85, 15), px(-100), px(0), paddle()), None);
+}
+
+#[test]
+fn overlap_pushes_out_through_the_shallowest_face() {
+    let hit = sweep(square(5, 172, 15), px(-1), px(0), paddle()).unwrap();
+    assert_eq!(hit.time, Fixed::ZERO);
+    assert_eq!(hit.face, Face::Bottom);
+    assert_eq!((hit.x, hit.y), (px(5), px(180)));
+}
+
+#[test]
+fn tiny_velocity_does_not_overflow() {
+    let hit = sweep(square(40, 120, 15), px(-100), Fixed::from_ratio(1, 60_000), paddle()).unwrap();
+    assert_eq!(hit.face, Face::Right);
+    assert_eq!(hit.time, Fixed::from_ratio(1, 4));
+}
@@ -0,0 +1,570 @@
+use std::cell::RefCell;
+
+use pong::fixed::Fixed;
+use pong::rules::ServeRule;
+use pong::{Difficulty, Event, GameMode, MatchRules, Players, PongGame, Request, Side};
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 600;
+const FRAME_RATE: u32 = 60;
+
+thread_local! {
+    static EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record(event: Event) {
+    EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+fn events() -> Vec<Event> {
+    EVENTS.with(|events| events.borrow_mut().drain(..).collect())
+}
+
+/// A match started from the menu and served, with events recorded.
+fn start(players: Players) -> PongGame {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.on_event = record;
+    if players == Players::Two {
+        game.handle_key('s');
+    }
+    assert_eq!(game.handle_key('\n'), None);
+    assert_eq!((game.game_mode, game.players), (GameMode::Serve, players));
+    run_until(&mut game, GameMode::Playing);
+    events();
+    game
+}
+
+/// Updates the game until it reaches `mode`, which must take under ten seconds.
+fn run_until(game: &mut PongGame, mode: GameMode) {
+    for _ in 0..10 * FRAME_RATE {
+        if game.game_mode == mode {
+            return;
+        }
+        game.update();
+    }
+    panic!("stuck in {:?} waiting for {mode:?}", game.game_mode);
+}
+
+/// Puts the ball at (`x`, `y`) moving at (`dx`, `dy`) pixels per second.
+fn place_ball(game: &mut PongGame, x: i32, y: i32, dx: i32, dy: i32) {
+    game.ball_x = Fixed::from_int(x);
+    game.ball_y = Fixed::from_int(y);
+    game.ball_dx = Fixed::from_int(dx);
+    game.ball_dy = Fixed::from_int(dy);
+    game.ball_speed = Fixed::from_int(dx.abs());
+}
+
+#[test]
+fn menu_starts_a_match() {
+    let game = start(Players::Two);
+    assert_eq!((game.left_score, game.right_score), (0, 0));
+    assert_eq!(game.ball_speed(), game.physics.serve_speed);
+}
+
+#[test]
+fn same_seed_replays_the_same_serves() {
+    let serves = |seed| {
+        let mut game = start(Players::Two);
+        game.reseed(seed);
+        (0..5)
+            .map(|_| {
+                game.left_score = 3;
+                game.update();
+                game.handle_key('\n');
+                game.handle_key('\n');
+                run_until(&mut game, GameMode::Playing);
+                (game.ball_dx, game.ball_dy)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(serves(1234), serves(1234));
+    assert_ne!(serves(1234), serves(4321));
+}
+
+#[test]
+fn menu_quit_asks_the_host_to_shut_down() {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.handle_key('\x1b');
+    assert_eq!(game.handle_key('\n'), Some(Request::Quit));
+    assert_eq!(game.game_mode, GameMode::Menu);
+}
+
+#[test]
+fn pause_menu_quit_asks_the_host_to_shut_down() {
+    let mut game = start(Players::One);
+    game.handle_key('p');
+    for _ in 0..4 {
+        assert_eq!(game.handle_key('s'), None);
+    }
+    assert_eq!(game.handle_key('\n'), Some(Request::Quit));
+    assert_eq!(game.game_mode, GameMode::Paused);
+}
+
+#[test]
+fn ball_leaving_the_left_edge_scores_for_the_right() {
+    let mut game = start(Players::Two);
+    game.left_paddle = 0;
+    place_ball(&mut game, -10, 400, -420, 0);
+    game.update();
+    assert_eq!((game.left_score, game.right_score), (0, 1));
+    assert_eq!(game.game_mode, GameMode::PointScored);
+    assert_eq!(events(), [Event::Point { side: Side::Right, left: 0, right: 1 }]);
+
+    run_until(&mut game, GameMode::Serve);
+    assert_eq!(game.ball_x, Fixed::from_int((WIDTH / 2) as i32));
+    assert_eq!(game.ball_dx, Fixed::ZERO);
+    run_until(&mut game, GameMode::Playing);
+    assert!(matches!(events()[..], [Event::Serve { .. }]));
+}
+
+#[test]
+fn ball_leaving_the_right_edge_scores_for_the_left() {
+    let mut game = start(Players::Two);
+    game.right_paddle = 0;
+    place_ball(&mut game, WIDTH as i32 - 5, 400, 420, 0);
+    game.update();
+    assert_eq!((game.left_score, game.right_score), (1, 0));
+}
+
+#[test]
+fn ball_between_the_paddles_does_not_score() {
+    let mut game = start(Players::Two);
+    place_ball(&mut game, 300, 300, -420, 0);
+    for _ in 0..10 {
+        game.update();
+    }
+    assert_eq!((game.left_score, game.right_score), (0, 0));
+}
+
+#[test]
+fn third_point_ends_the_game_and_enter_returns_to_the_menu() {
+    let mut game = start(Players::Two);
+    game.right_score = 3;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    assert_eq!(game.winner, Some("PLAYER 2 WINS!"));
+    assert_eq!(events(), [
+        Event::SetOver { winner: Side::Right, left: 0, right: 3 },
+        Event::GameOver { winner: Side::Right, left: 0, right: 3 },
+    ]);
+
+    game.handle_key('w');
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Menu);
+}
+
+#[test]
+fn one_player_loss_is_credited_to_the_cpu() {
+    let mut game = start(Players::One);
+    game.right_score = 3;
+    game.update();
+    assert_eq!(game.winner, Some("CPU WINS!"));
+}
+
+#[test]
+fn set_win_starts_the_next_set_until_the_match_is_decided() {
+    let mut game = start(Players::Two);
+    game.rules = MatchRules { best_of: 3, ..MatchRules::DEFAULT };
+    game.left_score = 3;
+    game.right_score = 1;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::Serve);
+    assert_eq!((game.left_sets, game.right_sets), (1, 0));
+    assert_eq!((game.left_score, game.right_score), (0, 0));
+
+    game.right_score = 3;
+    game.update();
+    assert_eq!((game.left_sets, game.right_sets), (1, 1));
+    game.left_score = 3;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    assert_eq!(game.winner, Some("PLAYER 1 WINS!"));
+    assert_eq!((game.left_sets, game.right_sets), (2, 1));
+}
+
+#[test]
+fn timed_set_goes_to_the_leader_or_to_sudden_death() {
+    let mut game = start(Players::Two);
+    game.rules = MatchRules { target_score: 21, time_limit: Some(60), ..MatchRules::DEFAULT };
+    game.set_frames = 60 * FRAME_RATE - 1;
+    assert_eq!(game.seconds_left(), Some(1));
+    game.update();
+    assert!(game.time_up());
+
+    // Tied when time runs out: play continues until someone scores
+    game.update();
+    assert_eq!(game.game_mode, GameMode::Playing);
+    game.right_score = 1;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    assert_eq!(game.winner, Some("PLAYER 2 WINS!"));
+}
+
+#[test]
+fn settings_screen_changes_the_rules() {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.handle_key('s');
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Settings);
+
+    game.handle_key('d'); // points to win
+    game.handle_key('s');
+    game.handle_key('\n'); // win by two
+    game.handle_key('s');
+    game.handle_key('d'); // time limit
+    game.handle_key('s');
+    game.handle_key('a'); // sets, wrapping to the last option
+    game.handle_key('s');
+    game.handle_key('d'); // serve
+    assert_eq!(game.rules, MatchRules {
+        target_score: 4,
+        win_by_two: true,
+        time_limit: Some(60),
+        best_of: 7,
+        serve: ServeRule::Alternate,
+    });
+    game.handle_key('s');
+    game.handle_key('d'); // CPU difficulty
+    assert_eq!(game.difficulty, Difficulty::Hard);
+
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Menu);
+}
+
+#[test]
+fn set_ball_speed_keeps_the_direction() {
+    let mut game = start(Players::Two);
+    place_ball(&mut game, 400, 300, 420, 0);
+    game.set_ball_speed(500);
+    assert_eq!((game.ball_dx.round(), game.ball_dy.round(), game.ball_speed()), (500, 0, 500));
+
+    place_ball(&mut game, 400, 300, 300, -400);
+    game.ball_speed = Fixed::from_int(500);
+    game.set_ball_speed(250);
+    assert_eq!((game.ball_dx.round(), game.ball_dy.round(), game.ball_speed()), (150, -200, 250));
+}
+
+#[test]
+fn ball_bounces_off_the_top_wall() {
+    let mut game = start(Players::Two);
+    place_ball(&mut game, 400, 2, 300, -300);
+    game.update();
+    assert_eq!(game.ball_y, Fixed::ZERO);
+    assert_eq!(game.ball_dy, Fixed::from_int(300));
+    assert_eq!(events(), [Event::WallBounce { x: 405, dx: 300, dy: 300 }]);
+}
+
+#[test]
+fn ball_bounces_off_the_bottom_wall() {
+    let mut game = start(Players::Two);
+    let bottom = (HEIGHT - game.ball_size) as i32;
+    place_ball(&mut game, 400, bottom - 2, 300, 300);
+    game.update();
+    assert_eq!(game.ball_y, Fixed::from_int(bottom));
+    assert_eq!(game.ball_dy, Fixed::from_int(-300));
+}
+
+#[test]
+fn center_hit_returns_the_ball_straight_and_faster() {
+    let mut game = start(Players::Two);
+    game.left_paddle = 260;
+    place_ball(&mut game, 40, 0, -420, 0);
+    // Ball center level with the paddle center
+    game.ball_y = Fixed::from_ratio(2 * 300 - game.ball_size as i32, 2);
+    for _ in 0..5 {
+        game.update();
+    }
+    let speed = 420 + game.physics.speed_step;
+    assert_eq!(game.ball_speed(), speed);
+    assert_eq!(game.ball_dx, Fixed::from_int(speed));
+    assert_eq!(game.ball_dy, Fixed::ZERO);
+    assert_eq!(events(), [Event::PaddleHit { side: Side::Left, offset: 0, dx: speed, dy: 0 }]);
+}
+
+#[test]
+fn off_center_hit_deflects_toward_that_end() {
+    let mut game = start(Players::Two);
+    game.right_paddle = 260;
+    place_ball(&mut game, WIDTH as i32 - 60, 262, 420, 0);
+    for _ in 0..10 {
+        game.update();
+    }
+    assert!(game.ball_dx < Fixed::ZERO);
+    assert!(game.ball_dy < Fixed::ZERO, "a hit near the top should send the ball up");
+}
+
+#[test]
+fn fast_ball_does_not_tunnel_through_a_paddle() {
+    let mut game = start(Players::Two);
+    game.left_paddle = 260;
+    // 200 pixels per frame, far more than the paddle is wide
+    place_ball(&mut game, 100, 290, -12_000, 0);
+    game.update();
+    assert!(game.ball_dx > Fixed::ZERO);
+    assert_eq!(game.right_score, 0);
+    assert!(matches!(events()[..], [Event::PaddleHit { side: Side::Left, .. }]));
+}
+
+#[test]
+fn ball_landing_on_a_paddle_end_bounces_off_it() {
+    let mut game = start(Players::Two);
+    game.left_paddle = 300;
+    place_ball(&mut game, 0, 270, 0, 600);
+    game.update();
+    game.update();
+    assert!(game.ball_y + Fixed::from_int(game.ball_size as i32) <= Fixed::from_int(300));
+    assert!(game.ball_dy < Fixed::ZERO);
+}
+
+#[test]
+fn keys_move_the_paddles_within_the_field() {
+    let mut game = start(Players::Two);
+    let (left, right) = (game.left_paddle, game.right_paddle);
+    game.handle_key('w');
+    game.handle_key('k');
+    assert!(game.left_paddle < left);
+    assert!(game.right_paddle > right);
+    for _ in 0..100 {
+        game.handle_key('w');
+        game.handle_key('k');
+    }
+    assert_eq!(game.left_paddle, 0);
+    assert_eq!(game.right_paddle, (HEIGHT - game.paddle_height) as isize);
+}
+
+#[test]
+fn cpu_controls_the_right_paddle_in_one_player_mode() {
+    let mut game = start(Players::One);
+    let right = game.right_paddle;
+    game.handle_key('i');
+    assert_eq!(game.right_paddle, right);
+
+    game.right_paddle = 0;
+    place_ball(&mut game, 400, 500, 60, 0);
+    for _ in 0..FRAME_RATE / 2 {
+        game.update();
+    }
+    assert!(game.right_paddle > 0);
+}
+
+/// Leaves the menu idle until attract mode starts, with events recorded.
+fn idle_until_attract() -> PongGame {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.on_event = record;
+    for _ in 0..60 * FRAME_RATE {
+        if game.players == Players::Demo {
+            return game;
+        }
+        game.update();
+    }
+    panic!("attract mode never started");
+}
+
+#[test]
+fn idle_menu_starts_attract_mode() {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    for _ in 0..10 * FRAME_RATE {
+        game.update();
+    }
+    game.handle_key('w');
+    for _ in 0..10 * FRAME_RATE {
+        game.update();
+    }
+    assert_eq!(game.game_mode, GameMode::Menu, "a key press restarts the wait");
+
+    let game = idle_until_attract();
+    assert_eq!(game.game_mode, GameMode::Serve);
+}
+
+#[test]
+fn attract_mode_plays_both_sides_without_events() {
+    let mut game = idle_until_attract();
+    let (left, right) = (game.left_paddle, game.right_paddle);
+    for _ in 0..60 * FRAME_RATE {
+        game.update();
+        assert_eq!(game.players, Players::Demo);
+        assert_ne!(game.game_mode, GameMode::GameOver);
+    }
+    assert_ne!(game.left_paddle, left);
+    assert_ne!(game.right_paddle, right);
+    assert_eq!(events(), []);
+}
+
+#[test]
+fn any_key_leaves_attract_mode() {
+    let mut game = idle_until_attract();
+    game.handle_key('x');
+    assert_eq!(game.game_mode, GameMode::Menu);
+    assert_eq!(game.selected_menu_item, 0);
+}
+
+#[test]
+fn pause_freezes_the_match_until_resumed() {
+    let mut game = start(Players::One);
+    game.handle_key('p');
+    assert_eq!(game.game_mode, GameMode::Paused);
+    let (x, y, frames) = (game.ball_x, game.ball_y, game.set_frames);
+    game.update();
+    game.handle_key('s');
+    assert_eq!((game.ball_x, game.ball_y, game.set_frames), (x, y, frames));
+
+    game.handle_key('\x1b');
+    assert_eq!(game.game_mode, GameMode::Playing);
+    game.update();
+    assert_ne!(game.ball_x, x);
+}
+
+#[test]
+fn pause_menu_restarts_the_match() {
+    let mut game = start(Players::Two);
+    game.left_score = 2;
+    game.handle_key('\x1b');
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!((game.game_mode, game.players), (GameMode::Serve, Players::Two));
+    assert_eq!((game.left_score, game.right_score), (0, 0));
+}
+
+#[test]
+fn settings_opened_while_paused_return_to_the_pause_menu() {
+    let mut game = start(Players::One);
+    game.handle_key('p');
+    game.handle_key('s');
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Settings);
+    game.handle_key('\x1b');
+    assert_eq!(game.game_mode, GameMode::Paused);
+
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Menu);
+    // Settings opened from the menu go back to the menu
+    game.selected_menu_item = 2;
+    game.handle_key('\n');
+    game.handle_key('\x1b');
+    assert_eq!(game.game_mode, GameMode::Menu);
+}
+
+#[test]
+fn match_moves_through_serve_play_and_point_states() {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Serve);
+    game.update();
+    assert_eq!((game.game_mode, game.ball_dx), (GameMode::Serve, Fixed::ZERO), "the ball waits to be served");
+    run_until(&mut game, GameMode::Playing);
+    assert_ne!(game.ball_dx, Fixed::ZERO);
+
+    // The CPU's last point goes through PointScored before the results
+    game.right_score = 2;
+    game.left_paddle = 0;
+    place_ball(&mut game, -10, 400, -420, 0);
+    game.update();
+    assert_eq!(game.game_mode, GameMode::PointScored);
+    run_until(&mut game, GameMode::GameOver);
+    assert_eq!(game.winner, Some("CPU WINS!"));
+}
+
+#[test]
+fn match_stats_count_rallies_and_time() {
+    let mut game = start(Players::Two);
+    game.left_paddle = 260;
+    game.right_paddle = 0;
+    place_ball(&mut game, 40, 292, -420, 0);
+    run_until(&mut game, GameMode::PointScored);
+    assert_eq!(game.left_score, 1);
+    run_until(&mut game, GameMode::Playing);
+    game.left_paddle = 0;
+    place_ball(&mut game, -10, 400, -420, 0);
+    game.update();
+
+    let stats = game.stats;
+    assert_eq!((stats.rallies, stats.hits, stats.longest_rally), (2, 1, 1));
+    assert_eq!(stats.average_rally_tenths(), 5);
+    assert!(stats.seconds(FRAME_RATE) >= 2, "two serves and a point wait, plus the rally");
+}
+
+#[test]
+fn serve_counts_down_three_seconds_then_serves_away_from_the_loser() {
+    let mut game = start(Players::Two);
+    game.right_paddle = 0;
+    place_ball(&mut game, WIDTH as i32 - 5, 400, 420, 0);
+    game.update();
+    assert_eq!(game.server, Side::Right, "the loser of the point serves");
+    run_until(&mut game, GameMode::Serve);
+    events();
+
+    let mut frames = 0;
+    while game.game_mode == GameMode::Serve {
+        game.update();
+        frames += 1;
+    }
+    assert_eq!(frames, 3 * FRAME_RATE);
+    assert!(game.ball_dx < Fixed::ZERO);
+    assert!(matches!(events()[..], [Event::Serve { side: Side::Right, .. }]));
+}
+
+#[test]
+fn alternate_serve_rule_takes_turns() {
+    let mut game = start(Players::Two);
+    game.rules.serve = ServeRule::Alternate;
+    game.server = Side::Left;
+    game.right_paddle = 0;
+    place_ball(&mut game, WIDTH as i32 - 5, 400, 420, 0);
+    game.update();
+    assert_eq!(game.server, Side::Right, "the serve alternates");
+
+    // The left side wins the point again, so under the Loser rule the right would serve
+    run_until(&mut game, GameMode::Playing);
+    game.right_paddle = 0;
+    place_ball(&mut game, WIDTH as i32 - 5, 400, 420, 0);
+    game.update();
+    assert_eq!(game.left_score, 2);
+    assert_eq!(game.server, Side::Left, "the serve alternates");
+}
+
+#[test]
+fn attract_mode_does_not_change_seed_replays() {
+    let first_serve = |idle_frames| {
+        let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+        game.reseed(7);
+        for _ in 0..idle_frames {
+            game.update();
+        }
+        game.handle_key('x');
+        assert_eq!(game.game_mode, GameMode::Menu);
+        game.handle_key('\n');
+        run_until(&mut game, GameMode::Playing);
+        (game.server, game.ball_dx, game.ball_dy)
+    };
+    // Long enough for the demo to serve a few times
+    assert_eq!(first_serve(0), first_serve(60 * FRAME_RATE));
+}
+
+#[test]
+fn settings_from_the_pause_menu_keep_the_serve_countdown() {
+    let mut game = PongGame::new(WIDTH, HEIGHT, FRAME_RATE);
+    game.handle_key('\n');
+    for _ in 0..2 * FRAME_RATE {
+        game.update();
+    }
+    game.handle_key('p');
+    game.handle_key('s');
+    game.handle_key('s');
+    game.handle_key('\n');
+    assert_eq!(game.game_mode, GameMode::Settings);
+    game.handle_key('\x1b');
+    game.handle_key('\x1b');
+    assert_eq!(game.game_mode, GameMode::Serve);
+
+    let mut frames = 0;
+    while game.game_mode == GameMode::Serve {
+        game.update();
+        frames += 1;
+    }
+    assert_eq!(frames, FRAME_RATE, "the countdown carries on from 1");
+}
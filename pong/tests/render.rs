@@ -0,0 +1,163 @@
+use pong::render::{Color, Rect, Renderer, TextStyle};
+use pong::{GameMode, PongGame};
+
+/// Counts draw calls and remembers the text drawn.
+#[derive(Default)]
+struct Recorder {
+    clears: usize,
+    fills: usize,
+    blends: usize,
+    clips: Vec<Rect>,
+    text: Vec<String>,
+}
+
+impl Renderer for Recorder {
+    type Pixel = Color;
+
+    fn pixel(&self, color: Color) -> Color {
+        color
+    }
+
+    fn begin_frame(&mut self) {}
+
+    fn present(&mut self) {}
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clips.extend(clip);
+    }
+
+    fn clear_screen(&mut self, _color: Color) {
+        self.clears += 1;
+    }
+
+    fn fill_rect(&mut self, _x: usize, _y: usize, _width: usize, _height: usize, _color: Color) {
+        self.fills += 1;
+    }
+
+    fn fill_rect_blend(&mut self, _x: usize, _y: usize, _width: usize, _height: usize, _color: Color, _alpha: u8) {
+        self.blends += 1;
+    }
+
+    fn draw_vline(&mut self, _x: usize, _y: usize, _length: usize, _color: Color) {}
+
+    fn draw_string(&mut self, _x: usize, _y: usize, text: &str, _style: TextStyle, _color: Color) {
+        self.text.push(text.into());
+    }
+
+    fn draw_string_centered(&mut self, _y: usize, text: &str, _style: TextStyle, _color: Color) {
+        self.text.push(text.into());
+    }
+
+    fn draw_block_text(&mut self, _x: usize, _y: usize, text: &str, _scale: usize, _color: Color) {
+        self.text.push(text.into());
+    }
+
+    fn draw_block_text_centered(&mut self, _y: usize, text: &str, _scale: usize, _color: Color) {
+        self.text.push(text.into());
+    }
+}
+
+#[test]
+fn first_frame_draws_the_whole_menu() {
+    let mut game = PongGame::new(800, 600, 60);
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert_eq!(renderer.clears, 1);
+    assert!(renderer.text.iter().any(|text| text == "ULTRA PONG"));
+    assert!(renderer.text.iter().any(|text| text == "> 1 PLAYER <"));
+}
+
+#[test]
+fn unchanged_frame_draws_nothing() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.draw(&mut Recorder::default());
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert_eq!((renderer.clears, renderer.fills, renderer.text.len()), (0, 0, 0));
+}
+
+#[test]
+fn moving_ball_repaints_only_its_old_and_new_area() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.handle_key('s');
+    game.handle_key('\n');
+    while game.game_mode != GameMode::Playing {
+        game.update();
+    }
+    game.draw(&mut Recorder::default());
+
+    game.update();
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert_eq!(renderer.clears, 0);
+    assert_eq!(renderer.clips.len(), 2);
+    assert!(renderer.clips.iter().all(|clip| clip.width == game.ball_size));
+}
+
+#[test]
+fn pause_menu_dims_the_playfield() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.handle_key('\n');
+    game.draw(&mut Recorder::default());
+    game.handle_key('p');
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert_eq!(renderer.blends, 1);
+    assert!(renderer.text.iter().any(|text| text == "PAUSED"));
+    assert!(renderer.text.iter().any(|text| text == "> RESUME <"));
+}
+
+#[test]
+fn results_screen_shows_the_winner_and_match_stats() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.handle_key('\n');
+    game.right_score = 3;
+    game.update();
+    assert_eq!(game.game_mode, GameMode::GameOver);
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert!(renderer.text.iter().any(|text| text == "CPU WINS!"));
+    assert!(renderer.text.iter().any(|text| text == "LONGEST RALLY: 0 HITS"));
+    assert!(renderer.text.iter().any(|text| text == "MATCH TIME: 0:00"));
+}
+
+#[test]
+fn serve_countdown_repaints_only_its_band() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.handle_key('\n');
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert!(renderer.text.iter().any(|text| text == "3"));
+    assert!(renderer.text.iter().any(|text| text.ends_with(" SERVES")));
+
+    for _ in 0..60 {
+        game.update();
+    }
+    let mut renderer = Recorder::default();
+    game.draw(&mut renderer);
+    assert_eq!(renderer.clears, 0);
+    assert!(renderer.clips.iter().any(|clip| clip.width == 800));
+    assert!(renderer.text.iter().any(|text| text == "2"));
+}
+
+#[test]
+fn point_flash_blinks_until_the_next_serve() {
+    let mut game = PongGame::new(800, 600, 60);
+    game.handle_key('\n');
+    while game.game_mode != GameMode::Playing {
+        game.update();
+    }
+    game.ball_x = pong::fixed::Fixed::from_int(-100);
+    game.update();
+    assert_eq!(game.game_mode, GameMode::PointScored);
+
+    let mut shown = Vec::new();
+    while game.game_mode == GameMode::PointScored {
+        let mut renderer = Recorder::default();
+        game.draw(&mut renderer);
+        shown.push(renderer.text.iter().any(|text| text == "POINT!"));
+        game.update();
+    }
+    assert!(shown[0]);
+    assert!(shown.contains(&false), "the flash blinks");
+}
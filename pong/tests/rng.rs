@@ -0,0 +1,38 @@
+use pong::rng::Pcg32;
+
+#[test]
+fn matches_the_reference_implementation() {
+    // First outputs of pcg32-demo from the PCG reference code, seeded with (42, 54)
+    let mut rng = Pcg32::with_stream(42, 54);
+    let outputs: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+    assert_eq!(outputs, [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]);
+}
+
+#[test]
+fn same_seed_gives_the_same_sequence() {
+    let (mut a, mut b) = (Pcg32::new(7), Pcg32::new(7));
+    assert!((0..100).all(|_| a.next_u32() == b.next_u32()));
+    let (mut a, mut b) = (Pcg32::new(7), Pcg32::new(8));
+    assert!((0..100).any(|_| a.next_u32() != b.next_u32()));
+}
+
+#[test]
+fn below_stays_in_bounds_and_covers_them() {
+    let mut rng = Pcg32::new(1);
+    let mut counts = [0u32; 7];
+    for _ in 0..7000 {
+        counts[rng.below(7) as usize] += 1;
+    }
+    // Each bucket expects 1000; a fair generator stays well inside this
+    assert!(counts.iter().all(|&count| (850..1150).contains(&count)), "{counts:?}");
+}
+
+#[test]
+fn range_is_inclusive() {
+    let mut rng = Pcg32::new(2);
+    let values: Vec<i32> = (0..1000).map(|_| rng.range(-2, 2)).collect();
+    assert!(values.iter().all(|value| (-2..=2).contains(value)));
+    assert!(values.contains(&-2) && values.contains(&2));
+    assert_eq!(rng.range(5, 5), 5);
+    rng.range(i32::MIN, i32::MAX);
+}
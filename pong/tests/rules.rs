@@ -0,0 +1,49 @@
+use pong::rules::ServeRule;
+use pong::{MatchRules, Side};
+
+#[test]
+fn first_to_the_target_wins_the_set() {
+    let rules = MatchRules { target_score: 11, ..MatchRules::DEFAULT };
+    assert_eq!(rules.set_winner(10, 9, false), None);
+    assert_eq!(rules.set_winner(11, 10, false), Some(Side::Left));
+    assert_eq!(rules.set_winner(4, 11, false), Some(Side::Right));
+}
+
+#[test]
+fn win_by_two_plays_on_from_deuce() {
+    let rules = MatchRules { target_score: 11, win_by_two: true, ..MatchRules::DEFAULT };
+    assert_eq!(rules.set_winner(11, 10, false), None);
+    assert_eq!(rules.set_winner(11, 11, false), None);
+    assert_eq!(rules.set_winner(13, 11, false), Some(Side::Left));
+    assert_eq!(rules.set_winner(11, 9, false), Some(Side::Left));
+}
+
+#[test]
+fn time_up_ends_the_set_unless_tied() {
+    let rules = MatchRules { target_score: 21, time_limit: Some(60), ..MatchRules::DEFAULT };
+    assert_eq!(rules.set_winner(2, 1, false), None);
+    assert_eq!(rules.set_winner(2, 1, true), Some(Side::Left));
+    assert_eq!(rules.set_winner(2, 2, true), None);
+}
+
+#[test]
+fn best_of_counts_a_majority_of_sets() {
+    assert_eq!(MatchRules::DEFAULT.sets_to_win(), 1);
+    assert_eq!(MatchRules { best_of: 3, ..MatchRules::DEFAULT }.sets_to_win(), 2);
+    assert_eq!(MatchRules { best_of: 7, ..MatchRules::DEFAULT }.sets_to_win(), 4);
+}
+
+#[test]
+fn summary_describes_the_rules() {
+    assert_eq!(MatchRules::DEFAULT.summary(), "FIRST TO 3 POINTS");
+    let rules = MatchRules { target_score: 11, win_by_two: true, time_limit: Some(120), best_of: 3, ..MatchRules::DEFAULT };
+    assert_eq!(rules.summary(), "2 MIN SETS, FIRST TO 11, WIN BY 2, BEST OF 3");
+}
+
+#[test]
+fn serve_goes_to_the_loser_or_alternates() {
+    assert_eq!(ServeRule::Loser.next_server(Side::Left, Side::Left), Side::Right);
+    assert_eq!(ServeRule::Loser.next_server(Side::Left, Side::Right), Side::Left);
+    assert_eq!(ServeRule::Alternate.next_server(Side::Left, Side::Left), Side::Right);
+    assert_eq!(ServeRule::Alternate.next_server(Side::Right, Side::Left), Side::Left);
+}
@@ -1,6 +1,25 @@
+mod telemetry;
+
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 use ovmf_prebuilt::{Arch, FileType, Prebuilt, Source};
 
+/// Local TCP port QEMU serves COM2 on when telemetry is recorded.
+const TELEMETRY_PORT: u16 = 5556;
+
 fn main() {
+    // `cargo run -- --telemetry <file.csv|file.jsonl>` records the kernel's game events
+    let mut args = std::env::args().skip(1);
+    let mut telemetry_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--telemetry" => telemetry_path = Some(PathBuf::from(args.next().expect("--telemetry needs a file name"))),
+            other => panic!("unknown argument {other}"),
+        }
+    }
+
     // read env variables that were set in build script
     let uefi_path = env!("UEFI_PATH");
     println!("Using image: {}", uefi_path);
@@ -19,8 +38,33 @@ fn main() {
     // set kernel image
     cmd.arg("-drive").arg(format!("format=raw,file={uefi_path}"));
     cmd.arg("-serial").arg("stdio");
+    if telemetry_path.is_some() {
+        // The second -serial is COM2; QEMU waits for us to connect before starting the guest
+        cmd.arg("-serial").arg(format!("tcp:127.0.0.1:{TELEMETRY_PORT},server=on,wait=on"));
+    }
     
     // launch qemu and wait until it terminates
     let mut child = cmd.spawn().unwrap();
+    let recorder = telemetry_path.map(|path| thread::spawn(move || {
+        let stream = connect_telemetry();
+        match telemetry::record(stream, &path) {
+            Ok(summary) => eprintln!("Recorded {} telemetry events ({} malformed) to {}", summary.events, summary.malformed, path.display()),
+            Err(error) => eprintln!("Telemetry recording failed: {error}"),
+        }
+    }));
     child.wait().unwrap();
+    if let Some(recorder) = recorder {
+        recorder.join().unwrap();
+    }
+}
+
+/// Connects to QEMU's COM2 socket, retrying while QEMU starts up.
+fn connect_telemetry() -> TcpStream {
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(("127.0.0.1", TELEMETRY_PORT)) {
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("could not connect to QEMU's telemetry port {TELEMETRY_PORT}");
 }
\ No newline at end of file
@@ -0,0 +1,79 @@
+//! Host side of the kernel's telemetry stream. The kernel writes one flat JSON object per game
+//! event to COM2; `record` reads that stream and writes it to a file for balancing analysis,
+//! as CSV if the file name ends in `.csv` and as JSON Lines otherwise.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use serde_json::{Map, Value};
+
+/// CSV columns: every field any event can have, plus the host receive time.
+const COLUMNS: [&str; 11] = [
+    "host_ms", "tick", "event", "side", "offset", "x", "dx", "dy", "left_score", "right_score", "cycles",
+];
+
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+/// Counts reported once the stream ends.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub events: u64,
+    /// Lines that weren't a JSON object, e.g. cut short when QEMU exited.
+    pub malformed: u64,
+}
+
+/// Decodes events from `input` until it closes, writing each to `output` as it arrives.
+pub fn record(input: impl Read, output: &Path) -> io::Result<Summary> {
+    let format = match output.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => Format::Csv,
+        _ => Format::JsonLines,
+    };
+    let mut out = BufWriter::new(File::create(output)?);
+    if let Format::Csv = format {
+        writeln!(out, "{}", COLUMNS.join(","))?;
+    }
+
+    let start = Instant::now();
+    let mut summary = Summary::default();
+    for line in BufReader::new(input).split(b'\n') {
+        let line = line?;
+        let event = match serde_json::from_slice::<Value>(line.trim_ascii()) {
+            Ok(Value::Object(event)) => event,
+            _ => {
+                summary.malformed += 1;
+                continue;
+            }
+        };
+        let host_ms = start.elapsed().as_millis() as u64;
+        match format {
+            Format::Csv => write_csv_row(&mut out, host_ms, &event)?,
+            Format::JsonLines => {
+                let mut event = event;
+                event.insert("host_ms".into(), host_ms.into());
+                serde_json::to_writer(&mut out, &event)?;
+                writeln!(out)?;
+            }
+        }
+        // Keep the file current so it can be followed while the game runs
+        out.flush()?;
+        summary.events += 1;
+    }
+    Ok(summary)
+}
+
+fn write_csv_row(out: &mut impl Write, host_ms: u64, event: &Map<String, Value>) -> io::Result<()> {
+    write!(out, "{host_ms}")?;
+    for column in &COLUMNS[1..] {
+        match event.get(*column) {
+            Some(Value::String(text)) => write!(out, ",{text}")?,
+            Some(Value::Null) | None => write!(out, ",")?,
+            Some(value) => write!(out, ",{value}")?,
+        }
+    }
+    writeln!(out)
+}